@@ -0,0 +1,25 @@
+//! Draws the day 8 map as a Graphviz digraph on stdout. Run it as
+//! `cargo run --bin day8_dot -- <input> [--colour-cycles] [--collapse-chains]`; see
+//! [`aoc_2023::day8::DotOptions`] for what the flags do.
+
+use anyhow::{bail, Context, Result};
+use aoc_2023::day8::{write_dot, DotOptions};
+use std::io::{self, Write};
+
+fn main() -> Result<()> {
+    let usage = "Usage: day8_dot <input file> [--colour-cycles] [--collapse-chains]";
+    let mut args = std::env::args().skip(1);
+    let path = args.next().context(usage)?;
+    let mut options = DotOptions::default();
+    for arg in args {
+        match arg.as_str() {
+            "--colour-cycles" => options.colour_cycles = true,
+            "--collapse-chains" => options.collapse_chains = true,
+            _ => bail!("Unknown option {arg}\n{usage}"),
+        }
+    }
+    let input = std::fs::read_to_string(&path).with_context(|| format!("Couldn't read {path}"))?;
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    write_dot(&mut out, &input, options)?;
+    Ok(out.flush()?)
+}
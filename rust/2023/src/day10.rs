@@ -1,3 +1,4 @@
+use crate::utils::normalize_input;
 use anyhow::{Context, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::cell::Cell;
@@ -132,6 +133,7 @@ impl PipeGraph {
 
 #[aoc_generator(day10)]
 fn day10_gen(input: &str) -> PipeGraph {
+    let input = &*normalize_input(input);
     PipeGraph::from_input(input)
 }
 
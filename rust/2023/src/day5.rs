@@ -101,6 +101,96 @@ impl RangeMap {
             .unwrap_or(source)
     }
 
+    /// Splits `source` into pieces that each lie within a single `RangeOffset`, with anything
+    /// outside the map's ranges getting an identity offset.
+    fn split(&self, source: Range<i64>) -> Vec<RangeOffset> {
+        let mut out = Vec::new();
+        let mut cursor = source.start;
+        let first = self
+            .ranges
+            .partition_point(|range| range.bounds.end <= source.start);
+        for range in self.ranges[first..]
+            .iter()
+            .take_while(|range| range.bounds.start < source.end)
+        {
+            if cursor < range.bounds.start {
+                out.push(RangeOffset {
+                    offset: 0,
+                    bounds: cursor..range.bounds.start,
+                });
+                cursor = range.bounds.start;
+            }
+            let end = range.bounds.end.min(source.end);
+            out.push(RangeOffset {
+                offset: range.offset,
+                bounds: cursor..end,
+            });
+            cursor = end;
+        }
+        if cursor < source.end {
+            out.push(RangeOffset {
+                offset: 0,
+                bounds: cursor..source.end,
+            });
+        }
+        out
+    }
+
+    /// Maps every value in `source`, returning the (unsorted) output ranges.
+    fn map_range(&self, source: Range<i64>) -> impl Iterator<Item = Range<i64>> {
+        self.split(source)
+            .into_iter()
+            .map(|range| (range.bounds.start + range.offset)..(range.bounds.end + range.offset))
+    }
+
+    /// The map equivalent to applying `self` and then `next`.
+    fn compose(&self, next: &RangeMap) -> RangeMap {
+        let tail_start = self.ranges.last().map_or(0, |range| range.bounds.end);
+        let tail = RangeOffset {
+            offset: 0,
+            bounds: tail_start..i64::MAX,
+        };
+        let ranges = self
+            .ranges
+            .iter()
+            .chain([&tail])
+            .flat_map(|range| {
+                let image = (range.bounds.start + range.offset)..(range.bounds.end + range.offset);
+                next.split(image).into_iter().map(|piece| RangeOffset {
+                    offset: range.offset + piece.offset,
+                    bounds: (piece.bounds.start - range.offset)..(piece.bounds.end - range.offset),
+                })
+            })
+            .collect();
+        RangeMap::new(ranges)
+    }
+
+    /// The map taking each output back to its source, which only exists if the ranges' images
+    /// exactly tile the map's domain.
+    fn invert(&self) -> Result<RangeMap> {
+        let domain_end = self.ranges.last().map_or(0, |range| range.bounds.end);
+        let mut images: Vec<_> = self
+            .ranges
+            .iter()
+            .map(|range| RangeOffset {
+                offset: -range.offset,
+                bounds: (range.bounds.start + range.offset)..(range.bounds.end + range.offset),
+            })
+            .collect();
+        images.sort_unstable();
+        let tiles_domain = images.first().is_none_or(|range| range.bounds.start == 0)
+            && images
+                .windows(2)
+                .all(|pair| pair[0].bounds.end == pair[1].bounds.start)
+            && images
+                .last()
+                .is_none_or(|range| range.bounds.end == domain_end);
+        if !tiles_domain {
+            return Err(Error::msg("RangeMap is not a bijection"));
+        }
+        Ok(RangeMap::new(images))
+    }
+
     fn nom(input: &str) -> IResult<&str, Self> {
         separated_list0(newline, RangeOffset::nom)
             .map(Self::new)
@@ -145,6 +235,20 @@ impl Almanac {
         })
         .parse(input)
     }
+
+    /// All seven maps composed into one.
+    fn seed_to_location(&self) -> Result<RangeMap> {
+        let (first, rest) = self.maps.split_first().context("input was empty")?;
+        Ok(rest
+            .iter()
+            .fold(first.clone(), |composed, map| composed.compose(map)))
+    }
+
+    fn seed_ranges(&self) -> impl Iterator<Item = Range<i64>> + use<'_> {
+        self.seeds
+            .chunks_exact(2)
+            .map(|pair| pair[0]..(pair[0] + pair[1]))
+    }
 }
 
 #[aoc_generator(day5)]
@@ -169,55 +273,116 @@ fn day5_part1(input: &Almanac) -> Result<i64> {
 
 #[aoc(day5, part2)]
 fn day5_part2(input: &Almanac) -> Result<i64> {
-    let initial_ranges: Vec<_> = input
-        .seeds
-        .chunks_exact(2)
-        .map(|pair| (pair[0], pair[1]))
-        .collect();
-    let output_location_ranges: Vec<_> =
-        input
-            .maps
-            .iter()
-            .fold(initial_ranges, |source_ranges, map| {
-                source_ranges
-                    .into_iter()
-                    .flat_map(|source_range| {
-                        ((map
-                            .ranges
-                            .partition_point(|range| range.bounds.start <= source_range.0)
-                            - 1)..)
-                            .scan(Some(source_range), |maybe_source, idx| {
-                                if idx >= map.ranges.len() {
-                                    return maybe_source.take();
-                                }
-                                let Some((start, len)) = maybe_source else {
-                                    return None;
-                                };
-                                if *len == 0 {
-                                    return None;
-                                }
-
-                                let range = &map.ranges[idx];
-                                let dest_start = *start + range.offset;
-                                let dest_len = (range.bounds.end - *start).min(*len);
-                                *start += dest_len;
-                                *len -= dest_len;
-                                Some((dest_start, dest_len))
-                            })
-                    })
-                    .collect()
-            });
-    output_location_ranges
-        .into_iter()
-        .map(|(start, _)| start)
+    let seed_to_location = input.seed_to_location()?;
+    input
+        .seed_ranges()
+        .flat_map(|seeds| seed_to_location.map_range(seeds))
+        .map(|range| range.start)
         .min()
         .context("input was empty")
 }
 
+/// Works backwards from the locations instead: walks the pieces of the inverted map in order of
+/// location and stops at the first one that any seed lands in.
+#[aoc(day5, part2, Inverse)]
+fn day5_part2_inverse(input: &Almanac) -> Result<i64> {
+    let location_to_seed = input.seed_to_location()?.invert()?;
+    location_to_seed
+        .ranges
+        .iter()
+        .find_map(|piece| {
+            input
+                .seed_ranges()
+                .filter_map(|seeds| {
+                    let start = seeds.start.max(piece.bounds.start + piece.offset);
+                    let end = seeds.end.min(piece.bounds.end + piece.offset);
+                    (start < end).then_some(start - piece.offset)
+                })
+                .min()
+        })
+        .context("input was empty")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    static EXAMPLE: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+    #[test]
+    fn test_example() -> Result<()> {
+        let almanac = day5_gen(EXAMPLE)?;
+        assert_eq!(day5_part1(&almanac)?, 35);
+        assert_eq!(day5_part2(&almanac)?, 46);
+        assert_eq!(day5_part2_inverse(&almanac)?, 46);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compose_and_invert() -> Result<()> {
+        let almanac = day5_gen(EXAMPLE)?;
+        let composed = almanac.maps[1..]
+            .iter()
+            .fold(almanac.maps[0].clone(), |composed, map| {
+                composed.compose(map)
+            });
+        for seed in 0..200 {
+            let expected = almanac
+                .maps
+                .iter()
+                .fold(seed, |source, map| map.map(source));
+            assert_eq!(composed.map(seed), expected);
+            let mut pieces = composed.map_range(seed..seed + 1);
+            assert_eq!(pieces.next(), Some(expected..expected + 1));
+            assert_eq!(pieces.next(), None);
+        }
+
+        for map in &almanac.maps {
+            let inverse = map.invert()?;
+            for value in 0..200 {
+                assert_eq!(inverse.map(map.map(value)), value);
+            }
+        }
+        let lossy = RangeMap::new(vec![RangeOffset {
+            offset: 5,
+            bounds: 0..10,
+        }]);
+        assert!(lossy.invert().is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_range_map_new() {
         let ranges = vec![
@@ -1,5 +1,6 @@
 use crate::utils::*;
-use anyhow::{Context, Error, Result};
+use anyhow::{ensure, Context, Error, Result};
+use aoc_common::graph::Graph;
 use aoc_runner_derive::{aoc, aoc_generator};
 use core::hint::assert_unchecked;
 use nom::{
@@ -9,9 +10,11 @@ use nom::{
     sequence::{delimited, preceded, separated_pair},
     Parser,
 };
+use rustc_hash::FxBuildHasher;
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fmt::{Display, Write},
-    ops::Index,
+    io,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -32,7 +35,7 @@ impl Direction {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct NodeTag([u8; 3]);
 
 impl NodeTag {
@@ -100,80 +103,92 @@ impl Leaf {
     }
 }
 
-impl Index<Direction> for Leaf {
-    type Output = NodeTag;
-    fn index(&self, index: Direction) -> &Self::Output {
-        match index {
-            Direction::Left => &self.left,
-            Direction::Right => &self.right,
-        }
-    }
-}
-
-#[derive(Clone, Debug, Default)]
-struct InternalNode<T> {
-    children: [T; 26],
+/// The network, with each tag interned into a graph node whose edges are its `L` and `R` turns in
+/// that order.
+#[derive(Clone, Default)]
+struct TagMap {
+    graph: Graph<NodeTag, FxBuildHasher>,
 }
 
-impl<T: Default> InternalNode<T> {
+impl TagMap {
     fn new() -> Self {
         Self::default()
     }
-}
 
-type TagMap = InternalNode<InternalNode<InternalNode<Option<Leaf>>>>;
+    fn insert(&mut self, tag: NodeTag, value: Leaf) {
+        self.graph.add_edge(tag, value.left);
+        self.graph.add_edge(tag, value.right);
+    }
 
-impl TagMap {
-    fn insert(&mut self, tag: NodeTag, value: Leaf) -> Option<Leaf> {
-        tag.assume_bounds();
-        let first = &mut self.children[tag.0[0] as usize];
-        let second = &mut first.children[tag.0[1] as usize];
-        second.children[tag.0[2] as usize].replace(value)
-    }
-
-    fn get(&self, tag: NodeTag) -> Option<Leaf> {
-        tag.assume_bounds();
-        let first = &self.children[tag.0[0] as usize];
-        let second = &first.children[tag.0[1] as usize];
-        second.children[tag.0[2] as usize]
-    }
-
-    fn list_part2_nodes(&self) -> impl Iterator<Item = NodeTag> + use<'_> {
-        self.children.iter().enumerate().flat_map(|(a, first)| {
-            first
-                .children
-                .iter()
-                .enumerate()
-                .filter_map(move |(b, second)| {
-                    if second.children[0].is_some() {
-                        Some(NodeTag([a as u8, b as u8, 0]))
-                    } else {
-                        None
-                    }
-                })
-        })
+    fn id(&self, tag: NodeTag) -> Option<usize> {
+        self.graph.id(&tag)
+    }
+
+    fn tag(&self, id: usize) -> NodeTag {
+        *self.graph.label(id)
+    }
+
+    /// The node reached by turning `dir` at `id`.
+    fn step(&self, id: usize, dir: Direction) -> Result<usize> {
+        match *self.graph.neighbours(id) {
+            [left, right] => Ok(match dir {
+                Direction::Left => left,
+                Direction::Right => right,
+            }),
+            [] => Err(Error::msg(format!("Missing tag {}", self.tag(id)))),
+            _ => Err(Error::msg(format!("Tag {} is defined twice", self.tag(id)))),
+        }
+    }
+
+    /// The defined `..A` nodes, in tag order.
+    fn list_part2_nodes(&self) -> Vec<usize> {
+        let mut starts: Vec<usize> = (0..self.graph.len())
+            .filter(|&id| self.tag(id).0[2] == 0 && !self.graph.neighbours(id).is_empty())
+            .collect();
+        starts.sort_unstable_by_key(|&id| self.tag(id));
+        starts
     }
 
-    #[allow(unused)]
     fn entries(&self) -> impl Iterator<Item = (NodeTag, Leaf)> + use<'_> {
-        self.children.iter().enumerate().flat_map(|(a, first)| {
-            first
-                .children
-                .iter()
-                .enumerate()
-                .flat_map(move |(b, second)| {
-                    second.children.iter().copied().enumerate().filter_map(
-                        move |(c, maybe_leaf)| {
-                            maybe_leaf.map(|leaf| (NodeTag([a as u8, b as u8, c as u8]), leaf))
-                        },
-                    )
-                })
+        (0..self.graph.len()).filter_map(|id| match *self.graph.neighbours(id) {
+            [left, right] => Some((
+                self.tag(id),
+                Leaf {
+                    left: self.tag(left),
+                    right: self.tag(right),
+                },
+            )),
+            _ => None,
         })
     }
 }
 
+/// Checks the `L`/`R` header, the blank separator and the fixed-width `AAA = (BBB, CCC)` node lines
+/// before the parser and `TagMap` get to assume their bounds.
+fn validate(input: &str) -> Result<()> {
+    validate_lines(input, |line_no, line| match line_no {
+        0 => {
+            ensure!(!line.is_empty(), "Missing directions");
+            check_line_shape(line, line.len(), |_, byte| byte == b'L' || byte == b'R')
+        }
+        1 => {
+            ensure!(
+                line.is_empty(),
+                "Expected a blank line after the directions"
+            );
+            Ok(())
+        }
+        _ => check_line_shape(line, 16, |col, byte| match col {
+            0..=2 | 7..=9 | 12..=14 => byte.is_ascii_uppercase(),
+            _ => byte == b"AAA = (BBB, CCC)"[col],
+        }),
+    })
+}
+
 #[aoc_generator(day8)]
-fn day8_gen(input: &str) -> Result<(Vec<Direction>, Box<TagMap>)> {
+fn day8_gen(input: &str) -> Result<(Vec<Direction>, TagMap)> {
+    let input = &*normalize_input(input);
+    validate(input)?;
     run_parse(
         input,
         separated_pair(
@@ -184,7 +199,7 @@ fn day8_gen(input: &str) -> Result<(Vec<Direction>, Box<TagMap>)> {
                     newline,
                     ws(separated_pair(NodeTag::nom, tag(" = "), Leaf::nom)),
                 ),
-                || Box::new(TagMap::new()),
+                TagMap::new,
                 |mut tag_map, (tag, leaf)| {
                     tag_map.insert(tag, leaf);
                     tag_map
@@ -195,85 +210,488 @@ fn day8_gen(input: &str) -> Result<(Vec<Direction>, Box<TagMap>)> {
 }
 
 #[aoc(day8, part1)]
-fn day8_part1(input: &(Vec<Direction>, Box<TagMap>)) -> Result<u64> {
+fn day8_part1(input: &(Vec<Direction>, TagMap)) -> Result<u64> {
     let (dirs, map) = input;
 
     let mut steps = 0_u64;
-    let mut current_leaf = map.get(NodeTag([0; 3])).context("Missing starting node")?;
+    let mut current = map.id(NodeTag([0; 3])).context("Missing starting node")?;
     let mut iter = dirs.iter().copied().cycle();
 
     loop {
         let dir = iter.next().context("Cycle ended")?;
         steps += 1;
-        let next_tag = current_leaf[dir];
-        if next_tag.0 == [25; 3] {
+        current = map.step(current, dir)?;
+        if map.tag(current).0 == [25; 3] {
             break;
         }
-        current_leaf = map.get(next_tag).context("Missing a tag")?;
     }
 
     Ok(steps)
 }
 
 #[aoc(day8, part2)]
-fn day8_part2(input: &(Vec<Direction>, Box<TagMap>)) -> Result<u64> {
+fn day8_part2(input: &(Vec<Direction>, TagMap)) -> Result<u64> {
     let (dirs, map) = input;
 
-    let res = map
+    let cycles = map
         .list_part2_nodes()
-        .map(|mut node| {
-            let mut steps = 0_u64;
-            let mut dir_iter = dirs.iter().copied().cycle();
-            while node.0[2] != b'Z' - b'A' {
-                steps += 1;
-                node = take_step(map, node, &mut dir_iter)?;
+        .into_iter()
+        .map(|start| analyse_cycle(map, dirs, start))
+        .collect::<Result<Vec<_>>>()?;
+    ensure!(!cycles.is_empty(), "No starting tags given");
+    synchronise(&cycles)?.context("The ghosts never all reach a Z node together")
+}
+
+fn is_end(tag: NodeTag) -> bool {
+    tag.0[2] == b'Z' - b'A'
+}
+
+/// A ghost's position along with how far through the directions it is, which together determine
+/// everything it does next.
+type WalkState = (usize, usize);
+
+fn step_state(map: &TagMap, dirs: &[Direction], (id, idx): WalkState) -> Result<WalkState> {
+    Ok((map.step(id, dirs[idx])?, (idx + 1) % dirs.len()))
+}
+
+/// Brent's algorithm: the number of steps before `f` first enters its cycle from `start`, and the
+/// length of that cycle.
+fn brent<S: Copy + Eq>(start: S, f: impl Fn(S) -> Result<S>) -> Result<(u64, u64)> {
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start;
+    let mut hare = f(start)?;
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare;
+            power *= 2;
+            period = 0;
+        }
+        hare = f(hare)?;
+        period += 1;
+    }
+
+    let mut tortoise = start;
+    let mut hare = start;
+    for _ in 0..period {
+        hare = f(hare)?;
+    }
+    let mut tail = 0;
+    while tortoise != hare {
+        tortoise = f(tortoise)?;
+        hare = f(hare)?;
+        tail += 1;
+    }
+    Ok((tail, period))
+}
+
+/// Where one ghost is on a `..Z` node: at each step in `hits`, all below `tail + period`, and
+/// again every `period` steps after each hit that's at least `tail`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CycleInfo {
+    tail: u64,
+    period: u64,
+    hits: Vec<u64>,
+}
+
+impl CycleInfo {
+    fn is_hit(&self, step: u64) -> bool {
+        let step = if step < self.tail {
+            step
+        } else {
+            self.tail + (step - self.tail) % self.period
+        };
+        self.hits.binary_search(&step).is_ok()
+    }
+}
+
+fn analyse_cycle(map: &TagMap, dirs: &[Direction], start: usize) -> Result<CycleInfo> {
+    ensure!(!dirs.is_empty(), "No directions given");
+    let (tail, period) = brent((start, 0), |state| step_state(map, dirs, state))?;
+    let mut state = (start, 0);
+    let mut hits = Vec::new();
+    for step in 0..tail + period {
+        if is_end(map.tag(state.0)) {
+            hits.push(step);
+        }
+        state = step_state(map, dirs, state)?;
+    }
+    Ok(CycleInfo { tail, period, hits })
+}
+
+/// The first step at which every ghost is on a `..Z` node, if there ever is one.
+fn synchronise(cycles: &[CycleInfo]) -> Result<Option<u64>> {
+    // Before every ghost is in its cycle, just check each step.
+    let longest_tail = cycles.iter().map(|cycle| cycle.tail).max().unwrap_or(0);
+    if let Some(step) = (0..longest_tail).find(|&step| cycles.iter().all(|c| c.is_hit(step))) {
+        return Ok(Some(step));
+    }
+
+    // After that each ghost needs the step to match one of its hits modulo its period. Merge the
+    // ghosts in one at a time, keeping every congruence consistent with all of them so far.
+    let mut solutions: Vec<(u128, u128)> = vec![(0, 1)];
+    for cycle in cycles {
+        let mut merged = Vec::new();
+        for &(a, n) in &solutions {
+            for &hit in cycle.hits.iter().filter(|&&hit| hit >= cycle.tail) {
+                let period = cycle.period as u128;
+                if let Some((b, m)) = crt(a, n, hit as u128 % period, period) {
+                    ensure!(
+                        m <= u64::MAX as u128,
+                        "The ghosts' combined period overflowed"
+                    );
+                    merged.push((b, m));
+                }
             }
-            Ok::<u64, Error>(steps)
-        })
-        .try_fold(0, |a, b| if a == 0 { b } else { Ok(lcm(a, b?)) })?;
-    if res == 0 {
-        Err(Error::msg("No starting tags given"))
+        }
+        merged.sort_unstable();
+        merged.dedup();
+        solutions = merged;
+    }
+
+    let floor = longest_tail as u128;
+    let mut best: Option<u64> = None;
+    for (residue, modulus) in solutions {
+        let step = if residue >= floor {
+            residue
+        } else {
+            residue + (floor - residue).div_ceil(modulus) * modulus
+        };
+        let step = u64::try_from(step).context("The answer overflowed")?;
+        best = Some(best.map_or(step, |best| best.min(step)));
+    }
+    Ok(best)
+}
+
+/// Returns `(g, x, y)` with `a * x + b * y == g == gcd(a, b)`.
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
     } else {
-        Ok(res)
+        let (g, x, y) = extended_gcd(b, a.rem_euclid(b));
+        (g, y, x - a.div_euclid(b) * y)
     }
 }
 
-fn take_step(
-    map: &TagMap,
-    current_tag: NodeTag,
-    direction: &mut impl Iterator<Item = Direction>,
-) -> Result<NodeTag> {
-    map.get(current_tag)
-        .context("Missing a tag")
-        .and_then(|leaf| Ok(leaf[direction.next().context("direction iter empty")?]))
+/// Merges `x = a (mod n)` and `x = b (mod m)` into one congruence modulo `lcm(n, m)`, or `None` if
+/// they contradict each other. The moduli needn't be coprime.
+fn crt(a: u128, n: u128, b: u128, m: u128) -> Option<(u128, u128)> {
+    let (g, p, _) = extended_gcd(n as i128, m as i128);
+    let diff = b as i128 - a as i128;
+    if diff % g != 0 {
+        return None;
+    }
+    // n * p = g (mod m), so stepping a by n * k with k = diff / g * p lands on b modulo m.
+    let m_over_g = m as i128 / g;
+    let k = ((diff / g).rem_euclid(m_over_g) * p.rem_euclid(m_over_g)).rem_euclid(m_over_g);
+    let lcm = n / g as u128 * m;
+    Some(((a + n * k as u128) % lcm, lcm))
+}
+
+/// What [`write_dot`] adds to the bare graph.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DotOptions {
+    /// Fill in the nodes on each ghost's cycle, a colour per ghost.
+    pub colour_cycles: bool,
+    /// Merge runs of nodes, each going the same way on `L` and `R` to a node with no other way
+    /// in, into single nodes.
+    pub collapse_chains: bool,
 }
 
-fn lcm(a: u64, b: u64) -> u64 {
-    let gcd = gcd(a, b);
-    a * (b / gcd)
+const CYCLE_COLOURS: [&str; 8] = [
+    "lightblue",
+    "lightsalmon",
+    "palegreen",
+    "plum",
+    "khaki",
+    "lightpink",
+    "aquamarine",
+    "wheat",
+];
+
+/// Writes the map in `input` as a Graphviz digraph with `L`/`R` labelled edges. `..A` nodes are
+/// boxes, `..Z` nodes are double circles, and `AAA` and `ZZZ` are drawn in bold. The `day8_dot`
+/// binary runs this on an input file.
+pub fn write_dot(out: impl io::Write, input: &str, options: DotOptions) -> Result<()> {
+    let (dirs, map) = day8_gen(input)?;
+    write_map_dot(out, &dirs, &map, options)
 }
 
-fn gcd(a: u64, b: u64) -> u64 {
-    let mut big = a.max(b);
-    let mut small = a.min(b);
-    while small > 0 {
-        big = big.rem_euclid(small);
-        std::mem::swap(&mut big, &mut small);
+fn write_map_dot(
+    mut out: impl io::Write,
+    dirs: &[Direction],
+    map: &TagMap,
+    options: DotOptions,
+) -> Result<()> {
+    let leaves: BTreeMap<NodeTag, Leaf> = map.entries().collect();
+    let is_start = |tag: NodeTag| tag.0[2] == 0;
+    let is_endpoint = |tag: NodeTag| is_start(tag) || is_end(tag);
+
+    let mut colours = BTreeMap::<NodeTag, &str>::new();
+    if options.colour_cycles {
+        for (start, colour) in map
+            .list_part2_nodes()
+            .into_iter()
+            .zip(CYCLE_COLOURS.iter().cycle())
+        {
+            let cycle = analyse_cycle(map, dirs, start)?;
+            let mut state = (start, 0);
+            for step in 0..cycle.tail + cycle.period {
+                if step >= cycle.tail {
+                    colours.entry(map.tag(state.0)).or_insert(colour);
+                }
+                state = step_state(map, dirs, state)?;
+            }
+        }
+    }
+
+    // Each chain is keyed by its head, which stands in for the whole chain, and maps to its tail.
+    let mut chains = BTreeMap::<NodeTag, NodeTag>::new();
+    let mut in_chain = BTreeSet::<NodeTag>::new();
+    if options.collapse_chains {
+        let mut predecessors = BTreeMap::<NodeTag, Vec<NodeTag>>::new();
+        for (&tag, leaf) in &leaves {
+            predecessors.entry(leaf.left).or_default().push(tag);
+            if leaf.right != leaf.left {
+                predecessors.entry(leaf.right).or_default().push(tag);
+            }
+        }
+        let single_predecessor = |tag: NodeTag| match predecessors.get(&tag).map(Vec::as_slice) {
+            Some(&[predecessor]) => Some(predecessor),
+            _ => None,
+        };
+        // The node `tag` can absorb, if it only ever goes there and is its only way in.
+        let absorbs = |tag: NodeTag| {
+            let leaf = leaves.get(&tag)?;
+            let next = leaf.left;
+            (leaf.left == leaf.right
+                && next != tag
+                && !is_endpoint(tag)
+                && !is_endpoint(next)
+                && single_predecessor(next) == Some(tag))
+            .then_some(next)
+        };
+        for &tag in leaves.keys() {
+            let absorbed = single_predecessor(tag).is_some_and(|p| absorbs(p) == Some(tag));
+            if absorbed || absorbs(tag).is_none() {
+                continue;
+            }
+            let mut tail = tag;
+            in_chain.insert(tag);
+            while let Some(next) = absorbs(tail) {
+                in_chain.insert(next);
+                tail = next;
+            }
+            chains.insert(tag, tail);
+        }
     }
-    big
+
+    writeln!(out, "digraph day8 {{")?;
+    for (&tag, leaf) in &leaves {
+        if in_chain.contains(&tag) && !chains.contains_key(&tag) {
+            continue;
+        }
+        let mut attributes = Vec::new();
+        if let Some(tail) = chains.get(&tag) {
+            attributes.push(format!("label=\"{tag}..{tail}\""));
+        }
+        if is_start(tag) {
+            attributes.push("shape=box".to_owned());
+        } else if is_end(tag) {
+            attributes.push("shape=doublecircle".to_owned());
+        }
+        if tag.0 == [0; 3] || tag.0 == [25; 3] {
+            attributes.push("penwidth=3".to_owned());
+        }
+        if let Some(colour) = colours.get(&tag) {
+            attributes.push(format!("style=filled, fillcolor={colour}"));
+        }
+        if !attributes.is_empty() {
+            writeln!(out, "  {tag} [{}];", attributes.join(", "))?;
+        }
+
+        let leaf = chains.get(&tag).map_or(*leaf, |tail| leaves[tail]);
+        if leaf.left == leaf.right {
+            writeln!(out, "  {tag} -> {} [label=\"L/R\"];", leaf.left)?;
+        } else {
+            writeln!(out, "  {tag} -> {} [label=L];", leaf.left)?;
+            writeln!(out, "  {tag} -> {} [label=R];", leaf.right)?;
+        }
+    }
+    writeln!(out, "}}")?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use std::fs::File;
-    use std::io::{BufWriter, Write};
+    use std::io::BufWriter;
     use std::path::Path;
 
     static INPUT: &'static str = include_str!("../input/2023/day8.txt");
+    static EXAMPLE: &str = "LR
+
+AAA = (BBB, XXX)
+BBB = (XXX, ZZZ)
+ZZZ = (BBB, XXX)
+XXX = (XXX, XXX)
+";
     static THIS_FILE: &'static str = file!();
 
     #[test]
+    fn validates_input() -> Result<()> {
+        assert_eq!(day8_part1(&day8_gen(EXAMPLE)?)?, 2);
+        assert_eq!(day8_part1(&day8_gen(&EXAMPLE.replace('\n', "\r\n"))?)?, 2);
+        assert!(day8_gen(&EXAMPLE.replace("ZZZ = (BBB", "ZZZ = (BBb")).is_err());
+        assert!(day8_gen(&EXAMPLE.replace("XXX)\n", "XXX) \n")).is_err());
+        assert!(day8_gen(&EXAMPLE.replace("LR\n\n", "LR\n")).is_err());
+        let twice = day8_gen(&EXAMPLE.replace("ZZZ = (BBB", "BBB = (BBB"))?;
+        assert!(day8_part1(&twice).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_part2_example() -> Result<()> {
+        let example = "LR
+
+QQA = (QQB, XXX)
+QQB = (XXX, QQZ)
+QQZ = (QQB, XXX)
+RRA = (RRB, XXX)
+RRB = (RRC, RRC)
+RRC = (RRZ, RRZ)
+RRZ = (RRB, RRB)
+XXX = (XXX, XXX)
+";
+        assert_eq!(day8_part2(&day8_gen(example)?)?, 6);
+        Ok(())
+    }
+
+    /// Steps every ghost at once until they're all on a `..Z` node.
+    fn brute_force(input: &(Vec<Direction>, TagMap)) -> Option<u64> {
+        let (dirs, map) = input;
+        let mut ids = map.list_part2_nodes();
+        let mut dir_iter = dirs.iter().copied().cycle();
+        for step in 0..1000 {
+            if ids.iter().all(|&id| is_end(map.tag(id))) {
+                return Some(step);
+            }
+            let dir = dir_iter.next().unwrap();
+            for id in &mut ids {
+                *id = map.step(*id, dir).unwrap();
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_part2_with_tails_and_repeated_hits() -> Result<()> {
+        // ABA enters a cycle of 4 after 2 steps and hits Z twice in it; BBA enters a cycle of 6
+        // after 1 step, also hitting Z twice.
+        let nodes = "
+ABA = (ACB, ACB)
+ACB = (ADZ, ADZ)
+ADZ = (AEB, AEB)
+AEB = (AFZ, AFZ)
+AFZ = (AGB, AGB)
+AGB = (ADZ, ADZ)
+BBA = (BCZ, BCZ)
+BCZ = (BDB, BDB)
+BDB = (BEB, BEB)
+BEB = (BFB, BFB)
+BFB = (BGB, BGB)
+BGB = (BHZ, BHZ)
+BHZ = (BCZ, BCZ)
+";
+        let input = day8_gen(&format!("L\n{nodes}"))?;
+        let (dirs, map) = &input;
+        assert_eq!(
+            analyse_cycle(map, dirs, map.id(NodeTag([0, 1, 0])).unwrap())?,
+            CycleInfo {
+                tail: 2,
+                period: 4,
+                hits: vec![2, 4]
+            }
+        );
+        assert_eq!(day8_part2(&input)?, 6);
+        assert_eq!(brute_force(&input), Some(6));
+
+        // CBA only hits Z on odd steps, while ABA only does on even ones.
+        let input = day8_gen(&format!(
+            "L\n{nodes}CBA = (CCZ, CCZ)\nCCZ = (CDB, CDB)\nCDB = (CCZ, CCZ)\n"
+        ))?;
+        assert_eq!(brute_force(&input), None);
+        assert!(day8_part2(&input).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt(2, 4, 0, 6), Some((6, 12)));
+        assert_eq!(crt(3, 5, 4, 7), Some((18, 35)));
+        assert_eq!(crt(1, 2, 0, 4), None);
+        assert_eq!(crt(0, 1, 5, 9), Some((5, 9)));
+    }
+
+    #[test]
+    fn test_write_dot() -> Result<()> {
+        let input = "L
+
+AAA = (BBB, CCC)
+BBB = (DDD, DDD)
+CCC = (DDD, DDD)
+DDD = (EEE, EEE)
+EEE = (ZZZ, ZZZ)
+ZZZ = (ZZZ, AAA)
+";
+        let mut dot = Vec::new();
+        write_dot(&mut dot, input, DotOptions::default())?;
+        assert_eq!(
+            String::from_utf8(dot)?,
+            "digraph day8 {
+  AAA [shape=box, penwidth=3];
+  AAA -> BBB [label=L];
+  AAA -> CCC [label=R];
+  BBB -> DDD [label=\"L/R\"];
+  CCC -> DDD [label=\"L/R\"];
+  DDD -> EEE [label=\"L/R\"];
+  EEE -> ZZZ [label=\"L/R\"];
+  ZZZ [shape=doublecircle, penwidth=3];
+  ZZZ -> ZZZ [label=L];
+  ZZZ -> AAA [label=R];
+}
+"
+        );
+
+        let mut dot = Vec::new();
+        let options = DotOptions {
+            colour_cycles: true,
+            collapse_chains: true,
+        };
+        write_dot(&mut dot, input, options)?;
+        assert_eq!(
+            String::from_utf8(dot)?,
+            "digraph day8 {
+  AAA [shape=box, penwidth=3];
+  AAA -> BBB [label=L];
+  AAA -> CCC [label=R];
+  BBB -> DDD [label=\"L/R\"];
+  CCC -> DDD [label=\"L/R\"];
+  DDD [label=\"DDD..EEE\"];
+  DDD -> ZZZ [label=\"L/R\"];
+  ZZZ [shape=doublecircle, penwidth=3, style=filled, fillcolor=lightblue];
+  ZZZ -> ZZZ [label=L];
+  ZZZ -> AAA [label=R];
+}
+"
+        );
+        Ok(())
+    }
+
+    /// Regenerates `output/day8.dot` from the real input; run it with `--ignored`.
+    #[test]
+    #[ignore]
     fn generate_dotgraph() -> Result<()> {
         let path = Path::new(THIS_FILE)
             .parent()
@@ -281,16 +699,11 @@ mod test {
             .parent()
             .unwrap()
             .join("output/day8.dot");
-        let mut f = BufWriter::new(File::create(path)?);
-        writeln!(f, "digraph day8 {{")?;
-
-        let (_, map) = day8_gen(INPUT)?;
-        for (tag, leaf) in map.entries() {
-            writeln!(f, "  {} -> {}", tag, leaf.left)?;
-            writeln!(f, "  {} -> {}", tag, leaf.right)?;
-        }
-
-        writeln!(f, "}}")?;
+        let options = DotOptions {
+            colour_cycles: true,
+            collapse_chains: false,
+        };
+        write_dot(BufWriter::new(File::create(path)?), INPUT, options)?;
         Ok(())
     }
 
@@ -301,24 +714,28 @@ mod test {
 
         for start in starting_nodes {
             let mut phase = 0;
-            let mut current = start;
-            let mut iter = dirs.iter().copied().cycle();
+            let mut state = (start, 0);
             loop {
                 phase += 1;
-                current = take_step(&map, current, &mut iter)?;
-                if current.0[2] == b'Z' - b'A' {
+                state = step_state(&map, &dirs, state)?;
+                if is_end(map.tag(state.0)) {
                     break;
                 }
             }
             let mut interval = 0;
             loop {
                 interval += 1;
-                current = take_step(&map, current, &mut iter)?;
-                if current.0[2] == b'Z' - b'A' {
+                state = step_state(&map, &dirs, state)?;
+                if is_end(map.tag(state.0)) {
                     break;
                 }
             }
-            println!("Start {} has interval {}, phase {}", start, interval, phase);
+            println!(
+                "Start {} has interval {}, phase {}",
+                map.tag(start),
+                interval,
+                phase
+            );
         }
 
         Ok(())
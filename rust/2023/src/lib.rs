@@ -8,7 +8,7 @@ mod day4;
 mod day5;
 mod day6;
 mod day7;
-mod day8;
+pub mod day8;
 mod day9;
 
 mod utils;
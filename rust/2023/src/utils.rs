@@ -1,4 +1,5 @@
 use anyhow::{Error, Result};
+pub use aoc_common::input::{check_line_shape, normalize_input, validate_lines};
 use nom::{
     character::complete::space0,
     error::{ParseError, VerboseError},
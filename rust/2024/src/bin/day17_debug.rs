@@ -0,0 +1,13 @@
+//! Interactive debugger for the day 17 VM. Run it as `cargo run --bin day17_debug -- <input>` and
+//! type commands on stdin; see [`aoc_2024::day17::debug`] for what they are.
+
+use anyhow::{Context, Result};
+use std::io;
+
+fn main() -> Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .context("Usage: day17_debug <input file>")?;
+    let input = std::fs::read_to_string(&path).with_context(|| format!("Couldn't read {path}"))?;
+    aoc_2024::day17::debug(&input, io::stdin().lock(), io::stdout().lock())
+}
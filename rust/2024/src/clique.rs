@@ -0,0 +1,485 @@
+//! Cliques in undirected graphs over any hashable node labels: every maximal clique by
+//! Bron–Kerbosch, every clique of a given size, and a largest clique. Adjacency is kept as one
+//! bitset row per node, so set operations are a word at a time, and the outer loops visit nodes in
+//! degeneracy order so the sets they start from stay small.
+
+use aoc_common::graph;
+use rayon::prelude::*;
+use rustc_hash::FxHasher;
+use std::{
+    hash::{BuildHasherDefault, Hash},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+type Bits = Vec<u64>;
+
+fn set(bits: &mut [u64], node: usize) {
+    bits[node / 64] |= 1 << (node % 64);
+}
+
+fn clear(bits: &mut [u64], node: usize) {
+    bits[node / 64] &= !(1 << (node % 64));
+}
+
+fn contains(bits: &[u64], node: usize) -> bool {
+    bits[node / 64] & (1 << (node % 64)) != 0
+}
+
+fn count(bits: &[u64]) -> usize {
+    bits.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+fn is_empty(bits: &[u64]) -> bool {
+    bits.iter().all(|&word| word == 0)
+}
+
+fn intersection(a: &[u64], b: &[u64]) -> Bits {
+    a.iter().zip(b).map(|(a, b)| a & b).collect()
+}
+
+fn intersection_count(a: &[u64], b: &[u64]) -> usize {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a & b).count_ones() as usize)
+        .sum()
+}
+
+fn first(bits: &[u64]) -> Option<usize> {
+    bits.iter()
+        .position(|&word| word != 0)
+        .map(|idx| idx * 64 + bits[idx].trailing_zeros() as usize)
+}
+
+fn nodes(bits: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    bits.iter().enumerate().flat_map(|(idx, &word)| {
+        let mut word = word;
+        std::iter::from_fn(move || {
+            (word != 0).then(|| {
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                idx * 64 + bit
+            })
+        })
+    })
+}
+
+/// An undirected graph without self-loops. Nodes are numbered `0..len()` in the order their labels
+/// were first seen, and cliques are reported as those numbers.
+#[derive(Clone, Debug)]
+pub struct Graph<L> {
+    nodes: graph::Graph<L, BuildHasherDefault<FxHasher>>,
+    words: usize,
+    adjacency: Vec<u64>,
+}
+
+impl<L: Hash + Eq + Clone> Graph<L> {
+    /// Builds the graph from its edges. Repeated edges are merged and self-loops are dropped, though
+    /// the node still counts.
+    pub fn from_edges(edges: impl IntoIterator<Item = (L, L)>) -> Self {
+        let mut nodes = graph::Graph::new();
+        for (a, b) in edges {
+            nodes.add_edge(a, b);
+        }
+        Self::from_graph(nodes)
+    }
+
+    pub fn index(&self, label: &L) -> Option<usize> {
+        self.nodes.id(label)
+    }
+}
+
+impl<L> Graph<L> {
+    /// Views a directed graph as undirected, keeping its node numbering: an edge either way joins two
+    /// nodes, and self-loops are dropped.
+    pub fn from_graph(nodes: graph::Graph<L, BuildHasherDefault<FxHasher>>) -> Self {
+        let words = nodes.len().div_ceil(64);
+        let mut adjacency = vec![0; nodes.len() * words];
+        for a in 0..nodes.len() {
+            for &b in nodes.neighbours(a) {
+                if a != b {
+                    set(&mut adjacency[a * words..(a + 1) * words], b);
+                    set(&mut adjacency[b * words..(b + 1) * words], a);
+                }
+            }
+        }
+        Graph {
+            nodes,
+            words,
+            adjacency,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn label(&self, node: usize) -> &L {
+        self.nodes.label(node)
+    }
+
+    pub fn labels<'a>(&'a self, nodes: &'a [usize]) -> impl Iterator<Item = &'a L> + 'a {
+        nodes.iter().map(|&node| self.label(node))
+    }
+
+    fn neighbours(&self, node: usize) -> &[u64] {
+        &self.adjacency[node * self.words..(node + 1) * self.words]
+    }
+
+    pub fn are_adjacent(&self, a: usize, b: usize) -> bool {
+        contains(self.neighbours(a), b)
+    }
+
+    pub fn degree(&self, node: usize) -> usize {
+        count(self.neighbours(node))
+    }
+
+    /// Whether every pair of `nodes` is joined by an edge.
+    pub fn is_clique(&self, nodes: &[usize]) -> bool {
+        nodes.iter().enumerate().all(|(idx, &a)| {
+            nodes[idx + 1..]
+                .iter()
+                .all(|&b| a != b && self.are_adjacent(a, b))
+        })
+    }
+
+    fn all_nodes(&self) -> Bits {
+        let mut bits = vec![0; self.words];
+        (0..self.len()).for_each(|node| set(&mut bits, node));
+        bits
+    }
+
+    /// Orders the nodes by repeatedly taking out one of least remaining degree. Each node then has
+    /// at most the graph's degeneracy neighbours later in the order, which bounds the candidate sets
+    /// the searches below start from.
+    pub fn degeneracy_order(&self) -> Vec<usize> {
+        let mut degrees: Vec<usize> = (0..self.len()).map(|node| self.degree(node)).collect();
+        let mut buckets = vec![Vec::new(); self.len()];
+        for (node, &degree) in degrees.iter().enumerate() {
+            buckets[degree].push(node);
+        }
+        let mut removed = vec![false; self.len()];
+        let mut order = Vec::with_capacity(self.len());
+        let mut lowest = 0;
+        while order.len() < self.len() {
+            // Entries go stale when a node's degree drops, so skip any that no longer match.
+            let Some(node) = buckets[lowest].pop() else {
+                lowest += 1;
+                continue;
+            };
+            if removed[node] || degrees[node] != lowest {
+                continue;
+            }
+            removed[node] = true;
+            order.push(node);
+            for neighbour in nodes(self.neighbours(node)) {
+                if !removed[neighbour] {
+                    degrees[neighbour] -= 1;
+                    buckets[degrees[neighbour]].push(neighbour);
+                    lowest = lowest.min(degrees[neighbour]);
+                }
+            }
+        }
+        order
+    }
+
+    /// Calls `f` with every maximal clique, each exactly once and in no particular order. An empty
+    /// graph has the empty clique.
+    pub fn for_each_maximal_clique(&self, mut f: impl FnMut(&[usize])) {
+        if self.is_empty() {
+            return f(&[]);
+        }
+        let mut later = self.all_nodes();
+        let mut earlier = vec![0; self.words];
+        let mut clique = Vec::new();
+        for node in self.degeneracy_order() {
+            clear(&mut later, node);
+            clique.push(node);
+            self.expand(
+                &mut clique,
+                intersection(&later, self.neighbours(node)),
+                intersection(&earlier, self.neighbours(node)),
+                &mut f,
+            );
+            clique.pop();
+            set(&mut earlier, node);
+        }
+    }
+
+    pub fn maximal_cliques(&self) -> Vec<Vec<usize>> {
+        let mut cliques = Vec::new();
+        self.for_each_maximal_clique(|clique| cliques.push(clique.to_vec()));
+        cliques
+    }
+
+    /// Bron–Kerbosch with Tomita's pivot: `clique` is the clique so far, `candidates` the nodes that
+    /// could extend it and `excluded` those that could too but whose cliques have all been reported.
+    fn expand(
+        &self,
+        clique: &mut Vec<usize>,
+        mut candidates: Bits,
+        mut excluded: Bits,
+        f: &mut impl FnMut(&[usize]),
+    ) {
+        if is_empty(&candidates) {
+            if is_empty(&excluded) {
+                f(clique);
+            }
+            return;
+        }
+        // Any maximal clique here contains the pivot or one of its non-neighbours, so only those
+        // need branching on. Picking the pivot covering the most candidates leaves the fewest.
+        let pivot = nodes(&candidates)
+            .chain(nodes(&excluded))
+            .max_by_key(|&node| intersection_count(&candidates, self.neighbours(node)))
+            .unwrap();
+        let branches: Vec<_> = nodes(&candidates)
+            .filter(|&node| !self.are_adjacent(pivot, node))
+            .collect();
+        for node in branches {
+            clique.push(node);
+            self.expand(
+                clique,
+                intersection(&candidates, self.neighbours(node)),
+                intersection(&excluded, self.neighbours(node)),
+                f,
+            );
+            clique.pop();
+            clear(&mut candidates, node);
+            set(&mut excluded, node);
+        }
+    }
+
+    /// Calls `f` with every clique of exactly `size` nodes, maximal or not, each once.
+    pub fn for_each_clique_of_size(&self, size: usize, mut f: impl FnMut(&[usize])) {
+        if size == 0 {
+            return f(&[]);
+        }
+        let mut later = self.all_nodes();
+        let mut clique = Vec::with_capacity(size);
+        // Each clique is found from whichever of its nodes comes first in the order.
+        for node in self.degeneracy_order() {
+            clear(&mut later, node);
+            clique.push(node);
+            self.extend_to(
+                &mut clique,
+                size,
+                intersection(&later, self.neighbours(node)),
+                &mut f,
+            );
+            clique.pop();
+        }
+    }
+
+    fn extend_to(
+        &self,
+        clique: &mut Vec<usize>,
+        size: usize,
+        mut candidates: Bits,
+        f: &mut impl FnMut(&[usize]),
+    ) {
+        if clique.len() == size {
+            return f(clique);
+        }
+        while let Some(node) = first(&candidates) {
+            if clique.len() + count(&candidates) < size {
+                return;
+            }
+            clear(&mut candidates, node);
+            clique.push(node);
+            self.extend_to(
+                clique,
+                size,
+                intersection(&candidates, self.neighbours(node)),
+                f,
+            );
+            clique.pop();
+        }
+    }
+
+    pub fn count_cliques_of_size(&self, size: usize) -> usize {
+        let mut total = 0;
+        self.for_each_clique_of_size(size, |_| total += 1);
+        total
+    }
+}
+
+impl<L: Sync> Graph<L> {
+    /// One of the largest cliques. The nodes in degeneracy order are searched in parallel, sharing
+    /// the best size found so far so that branches which can't beat it are cut off.
+    pub fn max_clique(&self) -> Vec<usize> {
+        let order = self.degeneracy_order();
+        let mut position = vec![0; self.len()];
+        for (idx, &node) in order.iter().enumerate() {
+            position[node] = idx;
+        }
+        let best_len = AtomicUsize::new(0);
+        order
+            .par_iter()
+            .filter_map(|&node| {
+                let mut later = vec![0; self.words];
+                nodes(self.neighbours(node))
+                    .filter(|&neighbour| position[neighbour] > position[node])
+                    .for_each(|neighbour| set(&mut later, neighbour));
+                let mut best = None;
+                self.grow(&mut vec![node], later, &best_len, &mut best);
+                best
+            })
+            .max_by_key(Vec::len)
+            .unwrap_or_default()
+    }
+
+    fn grow(
+        &self,
+        clique: &mut Vec<usize>,
+        mut candidates: Bits,
+        best_len: &AtomicUsize,
+        best: &mut Option<Vec<usize>>,
+    ) {
+        if is_empty(&candidates) {
+            if best_len.fetch_max(clique.len(), Ordering::Relaxed) < clique.len() {
+                *best = Some(clique.clone());
+            }
+            return;
+        }
+        while let Some(node) = first(&candidates) {
+            if clique.len() + count(&candidates) <= best_len.load(Ordering::Relaxed) {
+                return;
+            }
+            clear(&mut candidates, node);
+            clique.push(node);
+            self.grow(
+                clique,
+                intersection(&candidates, self.neighbours(node)),
+                best_len,
+                best,
+            );
+            clique.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::xorshift;
+    use rustc_hash::FxHashSet;
+
+    fn random_graph(nodes: usize, percent: u64, seed: u64) -> Graph<usize> {
+        let mut next = xorshift(seed);
+        let mut edges = Vec::new();
+        for a in 0..nodes {
+            edges.push((a, a));
+            for b in a + 1..nodes {
+                if next(100) < percent {
+                    edges.push((a, b));
+                }
+            }
+        }
+        Graph::from_edges(edges)
+    }
+
+    fn subsets(graph: &Graph<usize>) -> impl Iterator<Item = Vec<usize>> + '_ {
+        (0_u32..1 << graph.len()).map(|mask| {
+            (0..graph.len())
+                .filter(|&node| mask & (1 << node) != 0)
+                .collect()
+        })
+    }
+
+    fn sorted(mut cliques: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        cliques.iter_mut().for_each(|clique| clique.sort_unstable());
+        cliques.sort_unstable();
+        cliques
+    }
+
+    #[test]
+    fn test_labels_and_degeneracy() {
+        let graph = Graph::from_edges([("a", "b"), ("b", "c"), ("c", "a"), ("c", "d"), ("a", "b")]);
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.index(&"d"), Some(3));
+        assert_eq!(graph.index(&"e"), None);
+        assert_eq!(graph.degree(2), 3);
+        assert!(graph.is_clique(&[0, 1, 2]));
+        assert!(!graph.is_clique(&[0, 1, 3]));
+        let order = graph.degeneracy_order();
+        assert_eq!(order[0], 3);
+        assert_eq!(order.iter().copied().collect::<FxHashSet<_>>().len(), 4);
+        let clique = graph.max_clique();
+        let mut labels: Vec<_> = graph.labels(&clique).copied().collect();
+        labels.sort_unstable();
+        assert_eq!(labels, ["a", "b", "c"]);
+        assert_eq!(sorted(graph.maximal_cliques()), [vec![0, 1, 2], vec![2, 3]]);
+        assert_eq!(
+            Graph::<u8>::from_edges([]).max_clique(),
+            Vec::<usize>::new()
+        );
+
+        // A directed graph keeps its numbering, with edges either way and self-loops merged.
+        let mut directed = graph::Graph::new();
+        directed.add_edge("x", "y");
+        directed.add_edge("y", "x");
+        directed.add_edge("z", "z");
+        let graph = Graph::from_graph(directed);
+        assert_eq!(graph.index(&"z"), Some(2));
+        assert_eq!((graph.degree(0), graph.degree(2)), (1, 0));
+        assert_eq!(sorted(graph.maximal_cliques()), [vec![0, 1], vec![2]]);
+    }
+
+    #[test]
+    fn test_against_subsets() {
+        for seed in 1..40 {
+            let graph = random_graph(12, seed * 2 + 10, seed * 0x9E37_79B9);
+            let cliques: Vec<_> = subsets(&graph).filter(|set| graph.is_clique(set)).collect();
+            let maximal: Vec<_> = cliques
+                .iter()
+                .filter(|clique| {
+                    (0..graph.len())
+                        .filter(|node| !clique.contains(node))
+                        .all(|node| !clique.iter().all(|&c| graph.are_adjacent(c, node)))
+                })
+                .cloned()
+                .collect();
+            assert_eq!(sorted(graph.maximal_cliques()), sorted(maximal.clone()));
+            let largest = cliques.iter().map(Vec::len).max().unwrap();
+            let found = graph.max_clique();
+            assert!(graph.is_clique(&found));
+            assert_eq!(found.len(), largest);
+            for size in 0..=largest + 1 {
+                let mut of_size = Vec::new();
+                graph.for_each_clique_of_size(size, |clique| of_size.push(clique.to_vec()));
+                let expected: Vec<_> = cliques
+                    .iter()
+                    .filter(|c| c.len() == size)
+                    .cloned()
+                    .collect();
+                assert_eq!(sorted(of_size), sorted(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_graph() {
+        // Spans several words per row, with a planted clique among sparse noise.
+        let graph = random_graph(300, 3, 7);
+        let planted: Vec<usize> = (0..300).step_by(23).collect();
+        let mut edges: Vec<_> = (0..graph.len())
+            .flat_map(|a| (a + 1..graph.len()).map(move |b| (a, b)))
+            .filter(|&(a, b)| graph.are_adjacent(a, b))
+            .collect();
+        for (idx, &a) in planted.iter().enumerate() {
+            edges.extend(planted[idx + 1..].iter().map(|&b| (a, b)));
+        }
+        let graph = Graph::from_edges(edges);
+        let found = graph.max_clique();
+        assert!(graph.is_clique(&found));
+        assert_eq!(found.len(), planted.len());
+        let mut maximal_sizes = 0;
+        graph.for_each_maximal_clique(|clique| maximal_sizes = clique.len().max(maximal_sizes));
+        assert_eq!(maximal_sizes, planted.len());
+        assert_eq!(graph.count_cliques_of_size(planted.len()), 1);
+    }
+}
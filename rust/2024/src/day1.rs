@@ -9,6 +9,7 @@ use rdxsort::RdxSort;
 
 #[aoc(day1, part1)]
 pub fn part1(input: &str) -> u64 {
+    let input = &*normalize_input(input);
     let (mut left, mut right): (Vec<_>, Vec<_>) = input
         .lines()
         .map(|line| run_parse(line, separated_pair(u32, space1, u32)).unwrap())
@@ -24,6 +25,7 @@ pub fn part1(input: &str) -> u64 {
 
 #[aoc(day1, part1, Rdxsort)]
 pub fn part1_rdx(input: &str) -> u64 {
+    let input = &*normalize_input(input);
     let (mut left, mut right): (Vec<_>, Vec<_>) = input
         .lines()
         .map(|line| run_parse(line, separated_pair(u32, space1, u32)).unwrap())
@@ -39,6 +41,7 @@ pub fn part1_rdx(input: &str) -> u64 {
 
 #[aoc(day1, part1, Radsort)]
 pub fn part1_rad(input: &str) -> u64 {
+    let input = &*normalize_input(input);
     let (mut left, mut right): (Vec<_>, Vec<_>) = input
         .lines()
         .map(|line| run_parse(line, separated_pair(u32, space1, u32)).unwrap())
@@ -54,6 +57,7 @@ pub fn part1_rad(input: &str) -> u64 {
 
 #[aoc(day1, part2)]
 pub fn part2(input: &str) -> u64 {
+    let input = &*normalize_input(input);
     let mut list = Vec::new();
     let mut lookup = vec![0_u64; 100_000];
     input
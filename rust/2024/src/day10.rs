@@ -28,6 +28,7 @@ fn score_dfs2(pos: usize, stride: usize, input: &[u8]) -> usize {
 
 #[aoc(day10, part2)]
 pub fn part2(s: &str) -> usize {
+    let s = &*normalize_input(s);
     let stride = s.find("\n").unwrap() + 1;
     let input = s.as_bytes();
     input
@@ -70,6 +71,7 @@ fn score_dfs(pos: usize, stride: usize, input: &[u8], reachable: &mut FxHashSet<
 
 #[aoc(day10, part1)]
 pub fn part1(s: &str) -> usize {
+    let s = &*normalize_input(s);
     let stride = s.find("\n").unwrap() + 1;
     let input = s.as_bytes();
     let mut reachable = FxHashSet::default();
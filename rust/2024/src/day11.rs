@@ -1,9 +1,14 @@
 use super::*;
+use crate::memo::Memo;
+use arrayvec::ArrayVec;
 use rayon::{join, prelude::*};
 use rustc_hash::FxHashMap;
+use std::num::Wrapping;
+use std::ops::Add;
 
 #[aoc(day11, part1, Rayon)]
 pub fn part1_rayon(input: &str) -> u64 {
+    let input = &*normalize_input(input);
     input
         .split_ascii_whitespace()
         .map(|s| s.parse().unwrap())
@@ -38,7 +43,8 @@ fn calculate_stone_count(start: u64, remaining_blinks: u8) -> u64 {
 
 #[aoc(day11, part1, Cache)]
 pub fn part1_cache(input: &str) -> u64 {
-    let mut cache: FxHashMap<(u64, u8), u64> = FxHashMap::default();
+    let input = &*normalize_input(input);
+    let mut cache = Memo::new(FxHashMap::default());
     input
         .split_ascii_whitespace()
         .map(|s| s.parse().unwrap())
@@ -48,7 +54,8 @@ pub fn part1_cache(input: &str) -> u64 {
 
 #[aoc(day11, part2, Cache)]
 pub fn part2_cache(input: &str) -> u64 {
-    let mut cache: FxHashMap<(u64, u8), u64> = FxHashMap::default();
+    let input = &*normalize_input(input);
+    let mut cache = Memo::new(FxHashMap::default());
     input
         .split_ascii_whitespace()
         .map(|s| s.parse().unwrap())
@@ -56,40 +63,247 @@ pub fn part2_cache(input: &str) -> u64 {
         .sum()
 }
 
+type StoneCache = Memo<(u64, u8), u64, FxHashMap<(u64, u8), u64>>;
+
 fn calculate_stone_count_with_cache(
     start: u64,
     remaining_blinks: u8,
-    cache: &mut FxHashMap<(u64, u8), u64>,
+    cache: &mut StoneCache,
 ) -> u64 {
-    if remaining_blinks == 0 {
-        1
-    } else {
-        if let Some(count) = cache.get(&(start, remaining_blinks)) {
-            return *count;
-        }
-        let res = if start == 0 {
-            calculate_stone_count_with_cache(1, remaining_blinks - 1, cache)
-        } else {
+    cache.evaluate(
+        (start, remaining_blinks),
+        |&(start, remaining_blinks), cache| {
+            if remaining_blinks == 0 {
+                return Some(1);
+            }
+            if start == 0 {
+                return cache.get((1, remaining_blinks - 1));
+            }
             let digits = start.ilog10() + 1;
             if digits % 2 == 1 {
                 // odd number of digits, multiply
-                calculate_stone_count_with_cache(
-                    start.checked_mul(2024).unwrap(),
-                    remaining_blinks - 1,
-                    cache,
-                )
+                cache.get((start.checked_mul(2024).unwrap(), remaining_blinks - 1))
             } else {
                 // If there are N digits, then we want start % (10 ^ {N/2}) and floor(start / (10 ^ {N/2}))
                 let split = 10_u64.pow(digits / 2);
-                let low = start % split;
-                let high = start / split;
-                let low_count = calculate_stone_count_with_cache(low, remaining_blinks - 1, cache);
-                let high_count =
-                    calculate_stone_count_with_cache(high, remaining_blinks - 1, cache);
-                low_count.checked_add(high_count).unwrap()
+                let low_count = cache.get((start % split, remaining_blinks - 1))?;
+                let high_count = cache.get((start / split, remaining_blinks - 1))?;
+                Some(low_count.checked_add(high_count).unwrap())
+            }
+        },
+    )
+}
+
+/// A number of stones, which can be made to wrap for blink counts no integer could keep up with.
+pub trait StoneCount: Copy + Default + Add<Output = Self> {
+    const ONE: Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_stone_count {
+    ($($t:ty),*) => {$(
+        impl StoneCount for $t {
+            const ONE: Self = 1;
+
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$t>::checked_add(self, other)
+            }
+        }
+
+        impl StoneCount for Wrapping<$t> {
+            const ONE: Self = Wrapping(1);
+
+            fn checked_add(self, other: Self) -> Option<Self> {
+                Some(self + other)
             }
-        };
-        cache.insert((start, remaining_blinks), res);
-        res
+        }
+    )*};
+}
+
+impl_stone_count!(u64, u128);
+
+/// The most stones a single stone can turn into in one blink.
+pub const MAX_CHILDREN: usize = 4;
+
+/// What a stone turns into when you blink.
+pub trait BlinkRule {
+    fn blink(&self, stone: u64) -> Result<ArrayVec<u64, MAX_CHILDREN>>;
+}
+
+impl<F: Fn(u64) -> Result<ArrayVec<u64, MAX_CHILDREN>>> BlinkRule for F {
+    fn blink(&self, stone: u64) -> Result<ArrayVec<u64, MAX_CHILDREN>> {
+        self(stone)
+    }
+}
+
+/// The puzzle's rules: 0 becomes 1, stones with an even number of digits split into their two
+/// halves, and anything else is multiplied by `multiplier`.
+#[derive(Clone, Copy, Debug)]
+pub struct DigitRules {
+    pub multiplier: u64,
+}
+
+impl Default for DigitRules {
+    fn default() -> Self {
+        DigitRules { multiplier: 2024 }
+    }
+}
+
+impl BlinkRule for DigitRules {
+    fn blink(&self, stone: u64) -> Result<ArrayVec<u64, MAX_CHILDREN>> {
+        let mut children = ArrayVec::new();
+        if stone == 0 {
+            children.push(1);
+        } else if stone.ilog10() % 2 == 1 {
+            let split = 10_u64.pow(stone.ilog10().div_ceil(2));
+            children.push(stone / split);
+            children.push(stone % split);
+        } else {
+            children.push(
+                stone
+                    .checked_mul(self.multiplier)
+                    .with_context(|| format!("Stone {stone} overflows"))?,
+            );
+        }
+        Ok(children)
+    }
+}
+
+/// The stones as a multiset, since their order never matters and most of them are repeats.
+#[derive(Clone, Debug)]
+pub struct StoneHistogram<C> {
+    counts: FxHashMap<u64, C>,
+    scratch: FxHashMap<u64, C>,
+    blinks: usize,
+}
+
+impl<C: StoneCount> StoneHistogram<C> {
+    pub fn new(stones: impl IntoIterator<Item = u64>) -> Self {
+        let mut counts = FxHashMap::default();
+        for stone in stones {
+            let count: &mut C = counts.entry(stone).or_default();
+            *count = *count + C::ONE;
+        }
+        StoneHistogram {
+            counts,
+            scratch: FxHashMap::default(),
+            blinks: 0,
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let stones = s
+            .split_ascii_whitespace()
+            .map(|stone| {
+                stone
+                    .parse()
+                    .with_context(|| format!("Bad stone {stone:?}"))
+            })
+            .collect::<Result<Vec<u64>>>()?;
+        Ok(Self::new(stones))
+    }
+
+    pub fn blink(&mut self, rule: &impl BlinkRule) -> Result<()> {
+        self.scratch.clear();
+        for (&stone, &count) in &self.counts {
+            for child in rule.blink(stone)? {
+                let total = self.scratch.entry(child).or_default();
+                *total = total
+                    .checked_add(count)
+                    .context("Too many stones to count")?;
+            }
+        }
+        std::mem::swap(&mut self.counts, &mut self.scratch);
+        self.blinks += 1;
+        Ok(())
+    }
+
+    pub fn blink_times(&mut self, rule: &impl BlinkRule, blinks: usize) -> Result<()> {
+        for _ in 0..blinks {
+            self.blink(rule)?;
+        }
+        Ok(())
+    }
+
+    /// How many blinks have happened so far.
+    pub fn blinks(&self) -> usize {
+        self.blinks
+    }
+
+    /// How many of each stone there are right now.
+    pub fn distribution(&self) -> &FxHashMap<u64, C> {
+        &self.counts
+    }
+
+    pub fn total(&self) -> Result<C> {
+        self.counts
+            .values()
+            .try_fold(C::default(), |total, &count| {
+                total.checked_add(count).context("Too many stones to count")
+            })
+    }
+}
+
+#[aoc(day11, part1, Histogram)]
+pub fn part1_histogram(input: &str) -> Result<u64> {
+    let input = &*normalize_input(input);
+    let mut stones = StoneHistogram::parse(input)?;
+    stones.blink_times(&DigitRules::default(), 25)?;
+    stones.total()
+}
+
+#[aoc(day11, part2, Histogram)]
+pub fn part2_histogram(input: &str) -> Result<u64> {
+    let input = &*normalize_input(input);
+    let mut stones = StoneHistogram::parse(input)?;
+    stones.blink_times(&DigitRules::default(), 75)?;
+    stones.total()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static SITE_INPUT: &str = "125 17";
+
+    #[test]
+    fn test_histogram() -> Result<()> {
+        assert_eq!(part1_histogram(SITE_INPUT)?, 55312);
+        assert_eq!(part1_histogram("125 17\r\n")?, 55312);
+        assert_eq!(part1_cache(SITE_INPUT), 55312);
+        assert_eq!(part2_histogram(SITE_INPUT)?, part2_cache(SITE_INPUT));
+
+        let mut stones = StoneHistogram::<u64>::parse("0 1 10 99 999")?;
+        stones.blink(&DigitRules::default())?;
+        assert_eq!(stones.blinks(), 1);
+        assert_eq!(
+            *stones.distribution(),
+            FxHashMap::from_iter([(1, 2), (2024, 1), (0, 1), (9, 2), (2021976, 1)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_runs_and_other_rules() -> Result<()> {
+        let mut stones = StoneHistogram::<u128>::parse(SITE_INPUT)?;
+        assert!(stones.blink_times(&DigitRules::default(), 1000).is_err());
+        let mut stones = StoneHistogram::<Wrapping<u128>>::parse(SITE_INPUT)?;
+        stones.blink_times(&DigitRules::default(), 1000)?;
+        assert_eq!(stones.blinks(), 1000);
+        // Only a few thousand distinct stones ever turn up.
+        assert!(stones.distribution().len() < 5000);
+
+        // Each stone picks up 1 or not each blink, so the counts come out binomial.
+        let doubling = |stone: u64| Ok(ArrayVec::from_iter([stone, stone + 1]));
+        let mut stones = StoneHistogram::<u64>::parse(SITE_INPUT)?;
+        stones.blink_times(&doubling, 10)?;
+        assert_eq!(stones.total()?, 2 << 10);
+        assert_eq!(stones.distribution()[&130], 252);
+
+        let mut stones = StoneHistogram::<u64>::parse(SITE_INPUT)?;
+        stones.blink_times(&DigitRules { multiplier: 3 }, 5)?;
+        assert_eq!(stones.total()?, 8);
+        Ok(())
     }
 }
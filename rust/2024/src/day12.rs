@@ -3,6 +3,7 @@ use partitions::PartitionVec;
 
 #[aoc(day12, part1)]
 pub fn part1(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     let columns = s.find("\n").unwrap();
     let stride = columns + 1;
     let rows = (s.len() + 1) / stride;
@@ -106,6 +107,7 @@ pub fn part1(s: &str) -> u64 {
 
 #[aoc(day12, part2)]
 pub fn part2(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     unsafe { part2_impl(s) }
 }
 
@@ -1,4 +1,5 @@
 use super::*;
+use crate::linear;
 use nom::{
     bytes::complete::tag,
     character::complete::{newline, u64},
@@ -50,6 +51,44 @@ impl Machine {
             .parse(s)
     }
 
+    /// The fewest tokens that win the prize, if it can be won at all, found exactly. When the
+    /// buttons move the claw along the same line there can be many ways to reach the prize, and
+    /// the cheapest one is picked.
+    fn cheapest(&self) -> Result<Option<u64>> {
+        let [ax, ay, bx, by, px, py] = [
+            self.button_a.0,
+            self.button_a.1,
+            self.button_b.0,
+            self.button_b.1,
+            self.prize.0,
+            self.prize.1,
+        ]
+        .map(i128::from);
+        let presses = match linear::solve(&[vec![ax, bx], vec![ay, by]], &[px, py])? {
+            Some(solution) => solution
+                .integers()
+                .filter(|presses| presses.iter().all(|&n| n >= 0))
+                .map(|presses| (presses[0], presses[1])),
+            None => {
+                if ax * py != ay * px || bx * py != by * px {
+                    return Ok(None);
+                }
+                // Both buttons and the prize are on one line, so one axis the buttons move along
+                // settles the other.
+                if ax != 0 || bx != 0 {
+                    linear::cheapest_combination(ax, bx, px, 3, 1)?
+                } else if ay != 0 || by != 0 {
+                    linear::cheapest_combination(ay, by, py, 3, 1)?
+                } else {
+                    (self.prize == (0, 0)).then_some((0, 0))
+                }
+            }
+        };
+        presses
+            .map(|(a, b)| u64::try_from(3 * a + b).context("Too many tokens"))
+            .transpose()
+    }
+
     #[target_feature(enable = "avx2,bmi1,bmi2,cmpxchg16b,lzcnt,movbe,popcnt")]
     unsafe fn cramer(&self) -> u64 {
         let denom = (self.button_a.0 * self.button_b.1)
@@ -73,6 +112,7 @@ impl Machine {
 
 #[aoc(day13, part1, Dumb)]
 pub fn part1_dumb(s: &str) -> u32 {
+    let s = &*normalize_input(s);
     let iter = iterator(s, terminated(Machine::nom, opt((newline, newline))));
     iter.map(|machine| {
         let mut cheapest_win = None;
@@ -103,21 +143,155 @@ pub fn part1_dumb(s: &str) -> u32 {
     .sum()
 }
 
+fn parse(s: &str) -> Result<Vec<Machine>> {
+    s.trim_end()
+        .split("\n\n")
+        .enumerate()
+        .map(|(idx, block)| {
+            run_parse(block, Machine::nom).with_context(|| format!("Machine {}", idx + 1))
+        })
+        .collect()
+}
+
+const PRIZE_OFFSET: u64 = 10000000000000;
+
+fn total_tokens(machines: impl IntoIterator<Item = Machine>) -> Result<u64> {
+    machines
+        .into_iter()
+        .map(|machine| Ok(machine.cheapest()?.unwrap_or(0)))
+        .sum()
+}
+
+#[aoc(day13, part1)]
+pub fn part1(s: &str) -> Result<u64> {
+    let s = &*normalize_input(s);
+    total_tokens(parse(s)?)
+}
+
 #[aoc(day13, part1, Cramer)]
-pub fn part1(s: &str) -> u64 {
+pub fn part1_cramer(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     iterator(s, terminated(Machine::nom, opt((newline, newline))))
         .map(|machine| unsafe { machine.cramer() })
         .sum()
 }
 
 #[aoc(day13, part2)]
-pub fn part2(s: &str) -> u64 {
+pub fn part2(s: &str) -> Result<u64> {
+    let s = &*normalize_input(s);
+    total_tokens(parse(s)?.into_iter().map(|mut machine| {
+        machine.prize.0 += PRIZE_OFFSET;
+        machine.prize.1 += PRIZE_OFFSET;
+        machine
+    }))
+}
+
+#[aoc(day13, part2, Cramer)]
+pub fn part2_cramer(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     iterator(s, terminated(Machine::nom, opt((newline, newline))))
         .map(|mut machine| {
-            machine.prize.0 += 10000000000000;
-            machine.prize.1 += 10000000000000;
+            machine.prize.0 += PRIZE_OFFSET;
+            machine.prize.1 += PRIZE_OFFSET;
             machine
         })
         .map(|machine| unsafe { machine.cramer() })
         .sum()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+
+Button A: X+17, Y+86
+Button B: X+84, Y+37
+Prize: X=7870, Y=6450
+
+Button A: X+69, Y+23
+Button B: X+27, Y+71
+Prize: X=18641, Y=10279
+";
+
+    fn machine(button_a: (u64, u64), button_b: (u64, u64), prize: (u64, u64)) -> Machine {
+        Machine {
+            button_a,
+            button_b,
+            prize,
+        }
+    }
+
+    #[test]
+    fn test_exact() {
+        assert_eq!(part1(EXAMPLE).unwrap(), 480);
+        assert_eq!(part1(&EXAMPLE.replace('\n', "\r\n")).unwrap(), 480);
+        assert_eq!(part1_dumb(EXAMPLE), 480);
+        assert_eq!(part1_cramer(&EXAMPLE.replace('\n', "\r\n")), 480);
+        assert_eq!(part2(EXAMPLE).unwrap(), 875318608908);
+        assert!(part1("Button A: X+94, Y+34\nPrize: X=8400, Y=5400").is_err());
+    }
+
+    #[test]
+    fn test_collinear_buttons() {
+        // B moves twice as far for a third of the price, but A is needed to land on odd prizes.
+        assert_eq!(machine((1, 1), (2, 2), (5, 5)).cheapest().unwrap(), Some(5));
+        assert_eq!(machine((1, 1), (2, 2), (4, 4)).cheapest().unwrap(), Some(2));
+        assert_eq!(machine((4, 2), (2, 1), (7, 3)).cheapest().unwrap(), None);
+        assert_eq!(machine((4, 2), (6, 3), (7, 3)).cheapest().unwrap(), None);
+        assert_eq!(machine((1, 2), (2, 4), (3, 5)).cheapest().unwrap(), None);
+        // A goes far enough further that it's worth its price.
+        assert_eq!(
+            machine((5, 10), (1, 2), (15, 30)).cheapest().unwrap(),
+            Some(9)
+        );
+        assert_eq!(machine((0, 0), (0, 3), (0, 9)).cheapest().unwrap(), Some(3));
+        assert_eq!(machine((0, 0), (0, 0), (0, 0)).cheapest().unwrap(), Some(0));
+        assert_eq!(machine((0, 0), (0, 0), (0, 1)).cheapest().unwrap(), None);
+        assert_eq!(
+            machine((3, 3), (5, 5), (PRIZE_OFFSET, PRIZE_OFFSET))
+                .cheapest()
+                .unwrap(),
+            Some(PRIZE_OFFSET / 5)
+        );
+    }
+
+    #[test]
+    fn test_exact_matches_dumb() {
+        let mut next = xorshift(0x9E37_79B9_7F4A_7C15_u64);
+        for _ in 0..500 {
+            // With every move at least 5, no prize under 500 takes more than 100 presses of a
+            // button, which is as far as the brute force looks.
+            let button_a = (next(20) + 5, next(20) + 5);
+            let button_b = (next(20) + 5, next(20) + 5);
+            let prize = if next(2) == 0 {
+                let (a, b) = (next(21), next(21));
+                (
+                    button_a.0 * a + button_b.0 * b,
+                    button_a.1 * a + button_b.1 * b,
+                )
+            } else {
+                (next(500), next(500))
+            };
+            let machine = machine(button_a, button_b, prize);
+            let input = format!(
+                "Button A: X+{}, Y+{}\nButton B: X+{}, Y+{}\nPrize: X={}, Y={}",
+                button_a.0, button_a.1, button_b.0, button_b.1, prize.0, prize.1
+            );
+            // Collinear buttons can have a cheaper answer past the 100 press limit.
+            if button_a.0 * button_b.1 != button_a.1 * button_b.0 {
+                assert_eq!(
+                    part1(&input).unwrap(),
+                    part1_dumb(&input) as u64,
+                    "{machine:?}"
+                );
+            }
+        }
+    }
+}
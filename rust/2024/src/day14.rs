@@ -18,6 +18,7 @@ pub struct Robot {
 
 #[aoc_generator(day14)]
 fn day14_gen(s: &str) -> Result<Vec<Robot>> {
+    let s = &*normalize_input(s);
     run_parse(
         s,
         separated_list1(
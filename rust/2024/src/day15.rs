@@ -79,6 +79,7 @@ impl<T> IndexMut<(isize, isize)> for Grid<T> {
 
 #[aoc_generator(day15, part1)]
 fn day15_gen(s: &str) -> (Grid<Tile>, Vec<Direction>) {
+    let s = &*normalize_input(s);
     let mut parts = s.split("\n\n");
     let grid = parts.next().unwrap();
     let directions = parts.next().unwrap();
@@ -172,6 +173,7 @@ pub fn part1(input: &(Grid<Tile>, Vec<Direction>)) -> u64 {
 
 #[aoc_generator(day15, part2)]
 fn day15_gen_p2(s: &str) -> (Grid<Tile2>, Vec<Direction>) {
+    let s = &*normalize_input(s);
     let mut parts = s.split("\n\n");
     let grid = parts.next().unwrap();
     let directions = parts.next().unwrap();
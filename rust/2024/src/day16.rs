@@ -67,6 +67,7 @@ type ParsedInput = (
 
 #[aoc_generator(day16)]
 fn gen(s: &str) -> ParsedInput {
+    let s = &*normalize_input(s);
     let columns = s.find("\n").unwrap();
     let stride = columns + 1;
     let end_pos = Cell::new(None);
@@ -1,6 +1,13 @@
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+};
 
 use super::*;
+use crate::sat::{Bit, Lit, Solver};
+use anyhow::{bail, ensure};
+use arrayvec::ArrayVec;
+use rustc_hash::FxHashSet;
 
 use nom::{
     bytes::complete::{tag, take},
@@ -99,6 +106,14 @@ impl Display for Instruction {
     }
 }
 
+/// The `dv` instructions divide by a power of two, so shifting by 64 or more leaves nothing.
+fn shr(value: u64, amount: u64) -> u64 {
+    u32::try_from(amount)
+        .ok()
+        .and_then(|amount| value.checked_shr(amount))
+        .unwrap_or(0)
+}
+
 #[derive(Clone, Debug)]
 struct State {
     register_a: u64,
@@ -111,15 +126,15 @@ impl State {
     fn advance(&mut self, instruction: Instruction) -> Option<u8> {
         let res = match instruction {
             Instruction::Adv(combo) => {
-                self.register_a >>= self.combo_to_literal(combo);
+                self.register_a = shr(self.register_a, self.combo_to_literal(combo));
                 None
             }
             Instruction::Bdv(combo) => {
-                self.register_b = self.register_a >> self.combo_to_literal(combo);
+                self.register_b = shr(self.register_a, self.combo_to_literal(combo));
                 None
             }
             Instruction::Cdv(combo) => {
-                self.register_c = self.register_a >> self.combo_to_literal(combo);
+                self.register_c = shr(self.register_a, self.combo_to_literal(combo));
                 None
             }
             Instruction::Bxl(lit) => {
@@ -147,6 +162,14 @@ impl State {
         res
     }
 
+    fn register(&self, register: Register) -> u64 {
+        match register {
+            Register::A => self.register_a,
+            Register::B => self.register_b,
+            Register::C => self.register_c,
+        }
+    }
+
     fn combo_to_literal(&self, combo: Combo) -> u64 {
         match combo {
             Combo::Literal0 => 0,
@@ -160,13 +183,15 @@ impl State {
     }
 }
 
+/// Upper bound on executed instructions before we assume a program will never halt.
+const STEP_LIMIT: u64 = 1 << 24;
+
 fn parse_register(s: &str) -> StrIResult<'_, u64> {
     preceded((tag("Register "), take(1_usize), tag(": ")), u64).parse(s)
 }
 
-#[aoc(day17, part1)]
-pub fn part1(s: &str) -> Result<String> {
-    let register_parser = (
+fn parse_registers(s: &str) -> StrIResult<'_, State> {
+    (
         terminated(parse_register, newline),
         terminated(parse_register, newline),
         terminated(parse_register, newline),
@@ -176,45 +201,56 @@ pub fn part1(s: &str) -> Result<String> {
             register_b,
             register_c,
             program_counter: 0,
-        });
-    let (mut state, instructions) = run_parse(
+        })
+        .parse(s)
+}
+
+fn parse(s: &str) -> Result<(State, Vec<Instruction>)> {
+    run_parse(
         s,
         separated_pair(
-            register_parser,
+            parse_registers,
             newline,
             preceded(
                 tag("Program: "),
                 separated_list1(tag(","), Instruction::nom),
             ),
         ),
-    )?;
+    )
+}
+
+/// Runs `instructions` from `state` until the program counter leaves the program.
+fn run_to_halt(mut state: State, instructions: &[Instruction]) -> Result<Vec<u8>> {
     let mut output = Vec::new();
-    while let Some(instruction) = instructions.get(state.program_counter / 2) {
-        if let Some(new_output) = state.advance(*instruction) {
-            output.push(new_output.to_string());
-        }
+    let mut steps = 0;
+    while let Some(&instruction) = instructions.get(state.program_counter / 2) {
+        ensure!(
+            steps < STEP_LIMIT,
+            "Program didn't halt within {STEP_LIMIT} steps"
+        );
+        steps += 1;
+        output.extend(state.advance(instruction));
     }
+    Ok(output)
+}
 
-    Ok(output.join(","))
+#[aoc(day17, part1)]
+pub fn part1(s: &str) -> Result<String> {
+    let s = &*normalize_input(s);
+    let (state, instructions) = parse(s)?;
+    Ok(run_to_halt(state, &instructions)?
+        .into_iter()
+        .map(|output| output.to_string())
+        .collect::<Vec<_>>()
+        .join(","))
 }
 
-#[aoc(day17, part2)]
-pub fn part2(s: &str) -> Result<u64> {
-    let register_parser = (
-        terminated(parse_register, newline),
-        terminated(parse_register, newline),
-        terminated(parse_register, newline),
-    )
-        .map(|(register_a, register_b, register_c)| State {
-            register_a,
-            register_b,
-            register_c,
-            program_counter: 0,
-        });
+/// Parses the input along with the program's own digits, which part 2 wants it to output.
+fn parse_quine(s: &str) -> Result<(State, Vec<Instruction>, Vec<u8>)> {
     let (original_state, program) = run_parse(
         s,
         separated_pair(
-            register_parser,
+            parse_registers,
             newline,
             preceded(
                 tag("Program: "),
@@ -231,61 +267,871 @@ pub fn part2(s: &str) -> Result<u64> {
         })
         .collect();
     let instructions: Vec<Instruction> = program.into_iter().map(|(_, x)| x).collect();
-    recursive(
-        &original_state,
-        &target_output,
-        target_output.len() - 1,
-        0,
-        &instructions,
-    )
-    .context("Recursion failed to find answer")
+    Ok((original_state, instructions, target_output))
+}
+
+#[aoc(day17, part2)]
+pub fn part2(s: &str) -> Result<u64> {
+    let s = &*normalize_input(s);
+    let (original_state, instructions, target_output) = parse_quine(s)?;
+    solve_for_register_a(&original_state, &instructions, &target_output)?
+        .context("No value of register A produces the program")
+}
+
+/// The most output the compiled programs used by part 2 keep track of.
+const MAX_OUTPUT: usize = 32;
+
+#[aoc(day17, part2, Backtracking)]
+pub fn part2_backtracking(s: &str) -> Result<u64> {
+    let s = &*normalize_input(s);
+    let (original_state, instructions, target_output) = parse_quine(s)?;
+    ensure!(
+        target_output.len() <= MAX_OUTPUT,
+        "Program is longer than {MAX_OUTPUT} digits"
+    );
+    let result = match compile::<MAX_OUTPUT>(&instructions) {
+        Ok(program) => recursive(&target_output, target_output.len() - 1, 0, &|a| {
+            Ok(program(
+                a,
+                original_state.register_b,
+                original_state.register_c,
+            ))
+        })?,
+        Err(_) => recursive(&target_output, target_output.len() - 1, 0, &|a| {
+            let mut state = original_state.clone();
+            state.register_a = a;
+            // Anything too long to fit can't match the target, and nor can an empty output.
+            Ok(
+                ArrayVec::try_from(run_to_halt(state, &instructions)?.as_slice())
+                    .unwrap_or_default(),
+            )
+        })?,
+    };
+    result.context("Recursion failed to find answer")
 }
 
 fn recursive(
-    original_state: &State,
     target_output: &[u8],
     suffix_start: usize,
     base_a: u64,
+    run: &dyn Fn(u64) -> Result<ArrayVec<u8, MAX_OUTPUT>>,
+) -> Result<Option<u64>> {
+    for a in base_a << 3..(base_a << 3) + 8 {
+        let output = run(a)?;
+        if suffix_start == 0 {
+            if target_output == output.as_slice() {
+                return Ok(Some(a));
+            }
+        } else if target_output[suffix_start..] == *output {
+            let recursive_result = recursive(target_output, suffix_start - 1, a, run)?;
+            if recursive_result.is_some() {
+                return Ok(recursive_result);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// A combo operand with its literal values folded into constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Const(u64),
+    Register(usize),
+}
+
+impl Operand {
+    fn from_combo(combo: Combo) -> Self {
+        match combo {
+            Combo::Literal0 => Self::Const(0),
+            Combo::Literal1 => Self::Const(1),
+            Combo::Literal2 => Self::Const(2),
+            Combo::Literal3 => Self::Const(3),
+            Combo::RegisterA => Self::Register(0),
+            Combo::RegisterB => Self::Register(1),
+            Combo::RegisterC => Self::Register(2),
+        }
+    }
+
+    /// Where the operand lives in a compiled program's [`Slots`]. Combo literals only go up to 3,
+    /// so every constant has a slot of its own.
+    fn slot(self) -> usize {
+        match self {
+            Self::Const(value) => 3 + value as usize,
+            Self::Register(idx) => idx,
+        }
+    }
+}
+
+/// A single instruction of a compiled loop body, or a fused run of instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    /// `registers[dst] = a >> amount`
+    Shift {
+        dst: usize,
+        amount: Operand,
+    },
+    /// `b = (src % 8) ^ mask`, a `bst` with any `bxl`s after it folded into `mask`.
+    SetB {
+        src: Operand,
+        mask: u64,
+    },
+    /// `b = (src % 8) ^ mask; c = a >> b`, the `bst`, `bxl`, `cdv b` idiom most inputs use.
+    SetBShiftC {
+        src: Operand,
+        mask: u64,
+    },
+    XorB(u64),
+    XorBC,
+    Out(Operand),
+}
+
+/// Compiles a program that's a single loop, ending with `jnz 0` and shifting A right by a
+/// constant each time round, into a straight-line body that runs until A is zero.
+fn compile_ops(instructions: &[Instruction]) -> Result<Vec<Op>> {
+    let Some((Instruction::Jnz(0), body)) = instructions.split_last() else {
+        bail!("Program doesn't end by jumping back to the start");
+    };
+    let mut ops: Vec<Op> = Vec::with_capacity(body.len());
+    let mut terminates = false;
+    for &instruction in body {
+        let op = match instruction {
+            Instruction::Adv(combo) => {
+                let amount = Operand::from_combo(combo);
+                terminates |= matches!(amount, Operand::Const(1..));
+                Op::Shift { dst: 0, amount }
+            }
+            Instruction::Bdv(combo) => Op::Shift {
+                dst: 1,
+                amount: Operand::from_combo(combo),
+            },
+            Instruction::Cdv(Combo::RegisterB) => match ops.last() {
+                Some(&Op::SetB { src, mask }) => {
+                    ops.pop();
+                    Op::SetBShiftC { src, mask }
+                }
+                _ => Op::Shift {
+                    dst: 2,
+                    amount: Operand::Register(1),
+                },
+            },
+            Instruction::Cdv(combo) => Op::Shift {
+                dst: 2,
+                amount: Operand::from_combo(combo),
+            },
+            Instruction::Bxl(lit) => match ops.last_mut() {
+                Some(Op::SetB { mask, .. } | Op::XorB(mask)) => {
+                    *mask ^= lit as u64;
+                    continue;
+                }
+                _ => Op::XorB(lit as u64),
+            },
+            Instruction::Bxc => Op::XorBC,
+            Instruction::Bst(combo) => Op::SetB {
+                src: Operand::from_combo(combo),
+                mask: 0,
+            },
+            Instruction::Out(combo) => Op::Out(Operand::from_combo(combo)),
+            Instruction::Jnz(_) => bail!("Program jumps before the end of its loop"),
+        };
+        ops.push(op);
+    }
+    ensure!(
+        terminates,
+        "Program doesn't shift A by a constant every loop, so might not halt"
+    );
+    Ok(ops)
+}
+
+/// The registers A, B and C followed by the constants 0 to 3, so any operand is read by indexing.
+type Slots = [u64; 7];
+
+/// What's left of a compiled loop body. Returns `false` once the output is full.
+type Step<const N: usize> = Box<dyn Fn(&mut Slots, &mut ArrayVec<u8, N>) -> bool>;
+
+/// Compiles `instructions` into a function from the initial registers to the program's output.
+/// Each op becomes a closure specialised to its operands that calls on to the rest of the body,
+/// so running it never looks at an [`Op`] again. The function stops early once it has printed
+/// `N` values.
+fn compile<const N: usize>(
     instructions: &[Instruction],
-) -> Option<u64> {
-    if suffix_start == 0 {
-        for a in base_a << 3..(base_a << 3) + 8 {
-            let mut state = original_state.clone();
-            state.register_a = a;
-            let mut output = Vec::new();
-            while let Some(instruction) = instructions.get(state.program_counter / 2) {
-                if let Some(new_output) = state.advance(*instruction) {
-                    output.push(new_output);
+) -> Result<impl Fn(u64, u64, u64) -> ArrayVec<u8, N>> {
+    let mut body: Step<N> = Box::new(|_, _| true);
+    for op in compile_ops(instructions)?.into_iter().rev() {
+        let rest = body;
+        body = match op {
+            Op::Shift { dst, amount } => {
+                let amount = amount.slot();
+                Box::new(move |slots, output| {
+                    slots[dst] = shr(slots[0], slots[amount]);
+                    rest(slots, output)
+                })
+            }
+            Op::SetB { src, mask } => {
+                let src = src.slot();
+                Box::new(move |slots, output| {
+                    slots[1] = (slots[src] % 8) ^ mask;
+                    rest(slots, output)
+                })
+            }
+            Op::SetBShiftC { src, mask } => {
+                let src = src.slot();
+                Box::new(move |slots, output| {
+                    slots[1] = (slots[src] % 8) ^ mask;
+                    slots[2] = shr(slots[0], slots[1]);
+                    rest(slots, output)
+                })
+            }
+            Op::XorB(mask) => Box::new(move |slots, output| {
+                slots[1] ^= mask;
+                rest(slots, output)
+            }),
+            Op::XorBC => Box::new(move |slots, output| {
+                slots[1] ^= slots[2];
+                rest(slots, output)
+            }),
+            Op::Out(src) => {
+                let src = src.slot();
+                Box::new(move |slots, output| {
+                    output.try_push((slots[src] % 8) as u8).is_ok() && rest(slots, output)
+                })
+            }
+        };
+    }
+    Ok(move |a, b, c| {
+        let mut slots = [a, b, c, 0, 1, 2, 3];
+        let mut output = ArrayVec::new();
+        while body(&mut slots, &mut output) && slots[0] != 0 {}
+        output
+    })
+}
+
+/// Upper bound on symbolically executed instructions, summed over every explored path.
+const SYMBOLIC_STEP_LIMIT: u64 = 1 << 20;
+
+type Word = [Bit; 64];
+
+fn const_word(value: u64) -> Word {
+    std::array::from_fn(|bit| Bit::Const(value >> bit & 1 == 1))
+}
+
+/// `value >> amount`, where any amount of 64 or more shifts everything out.
+fn shift_right(solver: &mut Solver, value: &Word, amount: &Word) -> Word {
+    let mut out = *value;
+    for (stage, &select) in amount[..6].iter().enumerate() {
+        let distance = 1 << stage;
+        for bit in 0..64 {
+            let shifted = out
+                .get(bit + distance)
+                .copied()
+                .unwrap_or(Bit::Const(false));
+            out[bit] = solver.mux(select, shifted, out[bit]);
+        }
+    }
+    let overflow = amount[6..]
+        .iter()
+        .fold(Bit::Const(false), |acc, &bit| solver.or(acc, bit));
+    out.map(|bit| solver.and(!overflow, bit))
+}
+
+/// A path through the program with symbolic registers, and the conditions it relies on.
+#[derive(Clone, Debug)]
+struct SymbolicState {
+    registers: [Word; 3],
+    program_counter: usize,
+    outputs: usize,
+    constraints: Vec<Bit>,
+}
+
+impl SymbolicState {
+    fn combo(&self, combo: Combo) -> Word {
+        match combo {
+            Combo::Literal0 => const_word(0),
+            Combo::Literal1 => const_word(1),
+            Combo::Literal2 => const_word(2),
+            Combo::Literal3 => const_word(3),
+            Combo::RegisterA => self.registers[0],
+            Combo::RegisterB => self.registers[1],
+            Combo::RegisterC => self.registers[2],
+        }
+    }
+
+    /// Requires `bit` to hold on this path, returning `false` if it's already known not to.
+    fn require(&mut self, bit: Bit) -> bool {
+        match bit {
+            Bit::Const(holds) => holds,
+            Bit::Lit(_) => {
+                self.constraints.push(bit);
+                true
+            }
+        }
+    }
+}
+
+/// Finds the smallest non-zero initial register A that makes the program print exactly
+/// `target_output`, keeping registers B and C at their initial values.
+///
+/// Every instruction is executed over 64 bit-blasted variables for A, forking at each `jnz` whose
+/// condition isn't constant, so this makes no assumptions about the program's shape. Each path
+/// that halts after printing the target becomes a set of SAT assumptions, and A is minimised one
+/// bit at a time from the top.
+fn solve_for_register_a(
+    original_state: &State,
+    instructions: &[Instruction],
+    target_output: &[u8],
+) -> Result<Option<u64>> {
+    let mut solver = Solver::new();
+    let a_vars: [Lit; 64] = std::array::from_fn(|_| solver.new_var());
+    let a_word = a_vars.map(Bit::Lit);
+    let a_nonzero = a_word
+        .iter()
+        .fold(Bit::Const(false), |acc, &bit| solver.or(acc, bit));
+    let mut start = SymbolicState {
+        registers: [
+            a_word,
+            const_word(original_state.register_b),
+            const_word(original_state.register_c),
+        ],
+        program_counter: 0,
+        outputs: 0,
+        constraints: Vec::new(),
+    };
+    start.require(a_nonzero);
+
+    let mut best: Option<u64> = None;
+    let mut steps = 0_u64;
+    let mut paths = vec![start];
+    while let Some(mut state) = paths.pop() {
+        while let Some(&instruction) = instructions.get(state.program_counter / 2) {
+            steps += 1;
+            ensure!(
+                steps <= SYMBOLIC_STEP_LIMIT,
+                "Symbolic execution didn't finish within {SYMBOLIC_STEP_LIMIT} steps"
+            );
+            let [a, b, c] = state.registers;
+            match instruction {
+                Instruction::Adv(combo) => {
+                    state.registers[0] = shift_right(&mut solver, &a, &state.combo(combo))
+                }
+                Instruction::Bdv(combo) => {
+                    state.registers[1] = shift_right(&mut solver, &a, &state.combo(combo))
+                }
+                Instruction::Cdv(combo) => {
+                    state.registers[2] = shift_right(&mut solver, &a, &state.combo(combo))
+                }
+                Instruction::Bxl(lit) => {
+                    let lit = const_word(lit as u64);
+                    state.registers[1] = std::array::from_fn(|bit| solver.xor(b[bit], lit[bit]));
+                }
+                Instruction::Bxc => {
+                    state.registers[1] = std::array::from_fn(|bit| solver.xor(b[bit], c[bit]));
+                }
+                Instruction::Bst(combo) => {
+                    let value = state.combo(combo);
+                    state.registers[1] = std::array::from_fn(|bit| {
+                        if bit < 3 {
+                            value[bit]
+                        } else {
+                            Bit::Const(false)
+                        }
+                    });
+                }
+                Instruction::Out(combo) => {
+                    let Some(&digit) = target_output.get(state.outputs) else {
+                        break;
+                    };
+                    let value = state.combo(combo);
+                    let matches = (0..3).all(|bit| {
+                        let expected = digit >> bit & 1 == 1;
+                        state.require(if expected { value[bit] } else { !value[bit] })
+                    });
+                    if !matches {
+                        break;
+                    }
+                    state.outputs += 1;
+                }
+                Instruction::Jnz(lit) => {
+                    let nonzero = a
+                        .iter()
+                        .fold(Bit::Const(false), |acc, &bit| solver.or(acc, bit));
+                    match nonzero {
+                        Bit::Const(true) => {
+                            state.program_counter = lit as usize;
+                            continue;
+                        }
+                        Bit::Const(false) => {}
+                        Bit::Lit(_) => {
+                            let mut jumped = state.clone();
+                            jumped.require(nonzero);
+                            jumped.program_counter = lit as usize;
+                            paths.push(jumped);
+                            state.require(!nonzero);
+                        }
+                    }
                 }
             }
-            if target_output == output {
-                return Some(a);
+            state.program_counter += 2;
+        }
+
+        let halted = instructions.get(state.program_counter / 2).is_none();
+        if halted && state.outputs == target_output.len() {
+            if let Some(a) = minimise(&mut solver, &a_vars, &state.constraints) {
+                best = Some(best.map_or(a, |best| best.min(a)));
             }
         }
-        None
-    } else {
-        for a in base_a << 3..(base_a << 3) + 8 {
-            let mut state = original_state.clone();
-            state.register_a = a;
-            let mut output = Vec::new();
-            while let Some(instruction) = instructions.get(state.program_counter / 2) {
-                if let Some(new_output) = state.advance(*instruction) {
-                    output.push(new_output);
+    }
+    Ok(best)
+}
+
+/// The smallest value of the word `vars` satisfying `constraints`, if any.
+fn minimise(solver: &mut Solver, vars: &[Lit; 64], constraints: &[Bit]) -> Option<u64> {
+    let mut assumptions: Vec<Lit> = constraints
+        .iter()
+        .filter_map(|bit| match bit {
+            Bit::Lit(lit) => Some(*lit),
+            Bit::Const(_) => None,
+        })
+        .collect();
+    if !solver.solve(&assumptions) {
+        return None;
+    }
+    let mut value = 0;
+    let mut model_is_current = true;
+    for (bit, &var) in vars.iter().enumerate().rev() {
+        if model_is_current && !solver.value(var) {
+            assumptions.push(!var);
+            continue;
+        }
+        assumptions.push(!var);
+        model_is_current = solver.solve(&assumptions);
+        if !model_is_current {
+            assumptions.pop();
+            assumptions.push(var);
+            value |= 1 << bit;
+        }
+    }
+    Some(value)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Register {
+    A,
+    B,
+    C,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StopReason {
+    Halted,
+    Breakpoint(usize),
+    Watch {
+        register: Register,
+        old: u64,
+        new: u64,
+    },
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Halted => f.write_str("halted"),
+            StopReason::Breakpoint(pc) => write!(f, "breakpoint at pc {pc}"),
+            StopReason::Watch { register, old, new } => {
+                write!(f, "register {register:?} changed from {old:o} to {new:o}")
+            }
+        }
+    }
+}
+
+/// One executed instruction, along with the registers it left behind.
+#[derive(Clone, Debug)]
+struct TraceEntry {
+    step: u64,
+    program_counter: usize,
+    instruction: Instruction,
+    registers: [u64; 3],
+    output: Option<u8>,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c] = self.registers;
+        write!(
+            f,
+            "{:>6} pc={:<3} {:<18} a={a:o} b={b:o} c={c:o}",
+            self.step,
+            self.program_counter,
+            self.instruction.to_string()
+        )?;
+        if let Some(output) = self.output {
+            write!(f, " -> {output}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Single-steps a [`State`] through a program, stopping on program counter breakpoints or when a
+/// watched register changes, and optionally recording a trace of every executed instruction.
+#[derive(Clone, Debug)]
+struct Debugger<'a> {
+    instructions: &'a [Instruction],
+    state: State,
+    breakpoints: FxHashSet<usize>,
+    watches: Vec<Register>,
+    trace: Option<Vec<TraceEntry>>,
+    step_limit: Option<u64>,
+    steps: u64,
+    output: Vec<u8>,
+    at_breakpoint: bool,
+}
+
+impl<'a> Debugger<'a> {
+    fn new(state: State, instructions: &'a [Instruction]) -> Self {
+        Self {
+            instructions,
+            state,
+            breakpoints: FxHashSet::default(),
+            watches: Vec::new(),
+            trace: None,
+            step_limit: None,
+            steps: 0,
+            output: Vec::new(),
+            at_breakpoint: false,
+        }
+    }
+
+    /// Executes a single instruction, returning why execution should stop, if it should.
+    fn step(&mut self) -> Result<Option<StopReason>> {
+        self.at_breakpoint = false;
+        let program_counter = self.state.program_counter;
+        let Some(&instruction) = self.instructions.get(program_counter / 2) else {
+            return Ok(Some(StopReason::Halted));
+        };
+        if let Some(limit) = self.step_limit {
+            ensure!(
+                self.steps < limit,
+                "Program didn't halt within {limit} steps"
+            );
+        }
+
+        let before = self.state.clone();
+        let output = self.state.advance(instruction);
+        self.steps += 1;
+        self.output.extend(output);
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                step: self.steps,
+                program_counter,
+                instruction,
+                registers: [Register::A, Register::B, Register::C]
+                    .map(|register| self.state.register(register)),
+                output,
+            });
+        }
+
+        Ok(self.watches.iter().find_map(|&register| {
+            let old = before.register(register);
+            let new = self.state.register(register);
+            (old != new).then_some(StopReason::Watch { register, old, new })
+        }))
+    }
+
+    /// Steps until the program halts, hits a breakpoint or changes a watched register. Resuming
+    /// from a breakpoint executes the instruction it stopped on.
+    fn run(&mut self) -> Result<StopReason> {
+        loop {
+            let program_counter = self.state.program_counter;
+            if !self.at_breakpoint && self.breakpoints.contains(&program_counter) {
+                self.at_breakpoint = true;
+                return Ok(StopReason::Breakpoint(program_counter));
+            }
+            if let Some(reason) = self.step()? {
+                return Ok(reason);
+            }
+        }
+    }
+}
+
+/// Drives the day 17 VM from line-based `commands`, writing results to `out`. The `day17_debug`
+/// binary runs this over stdin and stdout:
+///
+/// - `s [n]`: step `n` instructions (default 1)
+/// - `c`: continue until halt, breakpoint or watch
+/// - `b <pc>`: toggle a breakpoint
+/// - `w <a|b|c>`: toggle a register watch
+/// - `t`: toggle tracing
+/// - `r`: print registers and output so far
+/// - `p`: list the program
+/// - `q`: quit
+pub fn debug(s: &str, commands: impl BufRead, mut out: impl Write) -> Result<()> {
+    let (state, instructions) = parse(s)?;
+    let mut debugger = Debugger::new(state, &instructions);
+    debugger.step_limit = Some(STEP_LIMIT);
+
+    for line in commands.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        let traced = debugger.trace.as_ref().map_or(0, Vec::len);
+        match (words.next(), words.next()) {
+            (None, _) => continue,
+            (Some("q"), _) => break,
+            (Some("s"), count) => {
+                let count: u64 = count.map_or(Ok(1), str::parse)?;
+                for _ in 0..count {
+                    if let Some(reason) = debugger.step()? {
+                        writeln!(out, "{reason}")?;
+                        break;
+                    }
                 }
             }
-            if target_output[suffix_start..] == output {
-                let recursive_result = recursive(
-                    original_state,
-                    target_output,
-                    suffix_start - 1,
-                    a,
-                    instructions,
-                );
-                if recursive_result.is_some() {
-                    return recursive_result;
+            (Some("c"), _) => writeln!(out, "{}", debugger.run()?)?,
+            (Some("b"), Some(pc)) => {
+                let pc: usize = pc.parse()?;
+                if !debugger.breakpoints.remove(&pc) {
+                    debugger.breakpoints.insert(pc);
+                }
+            }
+            (Some("w"), Some(register)) => {
+                let register = match register {
+                    "a" => Register::A,
+                    "b" => Register::B,
+                    "c" => Register::C,
+                    _ => bail!("Unknown register {register}"),
+                };
+                match debugger.watches.iter().position(|&r| r == register) {
+                    Some(idx) => {
+                        debugger.watches.remove(idx);
+                    }
+                    None => debugger.watches.push(register),
                 }
             }
+            (Some("t"), _) => {
+                debugger.trace = match debugger.trace {
+                    Some(_) => None,
+                    None => Some(Vec::new()),
+                }
+            }
+            (Some("r"), _) => {
+                let state = &debugger.state;
+                writeln!(
+                    out,
+                    "pc={} a={:o} b={:o} c={:o} output={:?}",
+                    state.program_counter,
+                    state.register_a,
+                    state.register_b,
+                    state.register_c,
+                    debugger.output
+                )?;
+            }
+            (Some("p"), _) => {
+                for (idx, instruction) in instructions.iter().enumerate() {
+                    writeln!(out, "{:>3}: {instruction}", idx * 2)?;
+                }
+            }
+            _ => bail!("Unknown command {line:?}"),
+        }
+        if let Some(trace) = &debugger.trace {
+            for entry in trace.iter().skip(traced) {
+                writeln!(out, "{entry}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "Register A: 729
+Register B: 0
+Register C: 0
+
+Program: 0,1,5,4,3,0
+";
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(EXAMPLE)?, "4,6,3,5,6,3,5,2,1,0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        let quine = "Register A: 2024
+Register B: 0
+Register C: 0
+
+Program: 0,3,5,4,3,0
+";
+        assert_eq!(part2(quine)?, 117440);
+        assert_eq!(part2_backtracking(quine)?, 117440);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_fuses_instructions() -> Result<()> {
+        let (_, instructions) = parse(
+            "Register A: 0
+Register B: 0
+Register C: 0
+
+Program: 2,4,1,3,7,5,1,5,0,3,4,1,5,5,3,0
+",
+        )?;
+        assert_eq!(
+            compile_ops(&instructions)?,
+            [
+                Op::SetBShiftC {
+                    src: Operand::Register(0),
+                    mask: 3
+                },
+                Op::XorB(5),
+                Op::Shift {
+                    dst: 0,
+                    amount: Operand::Const(3)
+                },
+                Op::XorBC,
+                Op::Out(Operand::Register(1)),
+            ]
+        );
+        // Never halts, since A is only shifted by B, which can be zero.
+        let (_, instructions) = parse(&EXAMPLE.replace("0,1,5,4", "0,5,5,4"))?;
+        assert!(compile_ops(&instructions).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_matches_interpreter() -> Result<()> {
+        let mut next = xorshift(0x2545_f491_4f6c_dd1d_u64);
+        for _ in 0..200 {
+            let mut program: Vec<u64> = Vec::new();
+            for _ in 0..next(6) {
+                let opcode = [1, 2, 4, 5, 6, 7][next(6) as usize];
+                program.extend([opcode, next(7)]);
+            }
+            // Make sure it halts, then jump back to the start.
+            let position = 2 * next(program.len() as u64 / 2 + 1) as usize;
+            program.splice(position..position, [0, 1 + next(3)]);
+            program.extend([3, 0]);
+            let (mut state, instructions) = parse(&format!(
+                "Register A: 0\nRegister B: 0\nRegister C: 0\n\nProgram: {}\n",
+                program
+                    .iter()
+                    .map(u64::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ))?;
+            let program = compile::<8>(&instructions)?;
+            for _ in 0..20 {
+                state.register_a = next(u64::MAX);
+                state.register_b = next(u64::MAX);
+                state.register_c = next(u64::MAX);
+                let mut expected = run_to_halt(state.clone(), &instructions)?;
+                expected.truncate(8);
+                assert_eq!(
+                    program(state.register_a, state.register_b, state.register_c).as_slice(),
+                    expected,
+                    "{instructions:?}"
+                );
+            }
         }
-        None
+        Ok(())
+    }
+
+    #[test]
+    fn test_solver_on_other_shapes() -> Result<()> {
+        // Shifts A by one bit per loop, which the octal backtracking can't handle.
+        let (state, instructions) = parse(EXAMPLE)?;
+        let target = run_to_halt(state.clone(), &instructions)?;
+        let expected = (1..)
+            .find(|&a| {
+                let mut state = state.clone();
+                state.register_a = a;
+                run_to_halt(state, &instructions).unwrap() == target
+            })
+            .unwrap();
+        assert_eq!(
+            solve_for_register_a(&state, &instructions, &target)?,
+            Some(expected)
+        );
+
+        // Carries B between loops, so C = A >> B can shift by any amount, and B starts non-zero.
+        let (mut state, instructions) = parse(
+            "Register A: 0
+Register B: 5
+Register C: 0
+
+Program: 1,3,7,5,4,5,0,3,5,5,3,0
+",
+        )?;
+        state.register_a = 0o7_1234_5670;
+        let target = run_to_halt(state.clone(), &instructions)?;
+        let a = solve_for_register_a(&state, &instructions, &target)?.unwrap();
+        assert!(a <= 0o7_1234_5670);
+        state.register_a = a;
+        assert_eq!(run_to_halt(state.clone(), &instructions)?, target);
+
+        assert_eq!(solve_for_register_a(&state, &instructions, &[8])?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_breakpoints_and_watches() -> Result<()> {
+        let (state, instructions) = parse(EXAMPLE)?;
+        let mut debugger = Debugger::new(state, &instructions);
+        debugger.breakpoints.insert(4);
+        assert_eq!(debugger.run()?, StopReason::Breakpoint(4));
+        assert_eq!(debugger.output, [4]);
+        assert_eq!(debugger.run()?, StopReason::Breakpoint(4));
+        assert_eq!(debugger.output, [4, 6]);
+
+        debugger.breakpoints.clear();
+        debugger.watches.push(Register::A);
+        assert_eq!(
+            debugger.run()?,
+            StopReason::Watch {
+                register: Register::A,
+                old: 182,
+                new: 91
+            }
+        );
+        debugger.watches.clear();
+        debugger.trace = Some(Vec::new());
+        assert_eq!(debugger.run()?, StopReason::Halted);
+        let trace = debugger.trace.unwrap();
+        assert_eq!(
+            trace.last().unwrap().to_string(),
+            "    30 pc=4   if a != 0: jmp 0   a=0 b=0 c=0"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_limit() -> Result<()> {
+        let forever = "Register A: 1
+Register B: 0
+Register C: 0
+
+Program: 3,0
+";
+        assert!(part1(forever).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_commands() -> Result<()> {
+        let mut out = Vec::new();
+        debug(EXAMPLE, "b 2\nt\nc\ns 2\nr\nq\ns\n".as_bytes(), &mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "\
+breakpoint at pc 2
+     1 pc=0   a = a >> 1         a=554 b=0 c=0
+     2 pc=2   print a            a=554 b=0 c=0 -> 4
+     3 pc=4   if a != 0: jmp 0   a=554 b=0 c=0
+pc=0 a=554 b=0 c=0 output=[4]
+"
+        );
+        Ok(())
     }
 }
@@ -4,6 +4,7 @@ use std::collections::BinaryHeap;
 
 #[aoc(day18, part1)]
 pub fn part1(s: &str) -> u32 {
+    let s = &*normalize_input(s);
     let mut grid = [[false; 71]; 71];
     s.lines().take(1024).for_each(|line| {
         let (x, y) = line.split_once(",").unwrap();
@@ -63,6 +64,7 @@ pub fn part1(s: &str) -> u32 {
 
 #[aoc(day18, part2)]
 pub fn part2(s: &str) -> String {
+    let s = &*normalize_input(s);
     let bytes: Vec<(usize, usize)> = s
         .lines()
         .map(|line| {
@@ -1,10 +1,13 @@
 use super::*;
+use crate::memo::{Memo, Store};
 
+use anyhow::ensure;
 use arrayvec::ArrayVec;
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use rayon::prelude::*;
 use rustc_hash::{FxBuildHasher, FxHashMap};
+use std::collections::VecDeque;
 use trie_rs::{
     map::{Trie, TrieBuilder},
     try_collect::TryFromIterator,
@@ -22,6 +25,7 @@ fn make_trie(basis: &str) -> Trie<u8, usize> {
 
 #[aoc_generator(day19)]
 fn gen(s: &str) -> (Trie<u8, usize>, String) {
+    let s = &*normalize_input(s);
     let (basis, targets) = s.split_once("\n\n").unwrap();
     let trie = make_trie(basis);
     (trie, targets.to_owned())
@@ -62,7 +66,7 @@ fn recursive_check(target: &[u8], basis_trie: &Trie<u8, usize>) -> bool {
 
 #[aoc(day19, part2)]
 pub fn part2((trie, targets): &(Trie<u8, usize>, String)) -> usize {
-    let count_cache: RwLock<FxHashMap<ArrayVec<u8, 60>, usize>> = RwLock::new(FxHashMap::default());
+    let count_cache: RwCache = RwLock::new(FxHashMap::default());
 
     targets
         .par_lines()
@@ -71,30 +75,15 @@ pub fn part2((trie, targets): &(Trie<u8, usize>, String)) -> usize {
         .sum()
 }
 
-fn recursive_sum(
-    target: &[u8],
-    basis_trie: &Trie<u8, usize>,
-    cache: &RwLock<FxHashMap<ArrayVec<u8, 60>, usize>>,
-) -> usize {
-    if let Some(cached) = cache.read().get(target) {
-        return *cached;
-    }
-    let mut sum = 0;
-    for (_, prefix_len) in basis_trie.common_prefix_search::<DropIter, _>(target) {
-        let new_target = &target[*prefix_len..];
-        if new_target.is_empty() {
-            sum += 1;
-        } else {
-            sum += recursive_sum(new_target, basis_trie, cache);
-        }
-    }
-    cache.write().insert(target.try_into().unwrap(), sum);
-    sum
+type RwCache = RwLock<FxHashMap<ArrayVec<u8, 60>, usize>>;
+
+fn recursive_sum(target: &[u8], basis_trie: &Trie<u8, usize>, cache: &RwCache) -> usize {
+    sum_with_memo(target, basis_trie, &mut Memo::new(cache))
 }
 
 #[aoc(day19, part2, Dash)]
 pub fn part2_dash((trie, targets): &(Trie<u8, usize>, String)) -> usize {
-    let count_cache: DashMap<ArrayVec<u8, 60>, usize, _> = DashMap::with_hasher(FxBuildHasher);
+    let count_cache: DashCache = DashMap::with_hasher(FxBuildHasher);
 
     targets
         .par_lines()
@@ -103,26 +92,319 @@ pub fn part2_dash((trie, targets): &(Trie<u8, usize>, String)) -> usize {
         .sum()
 }
 
-fn recursive_sum_dash(
+type DashCache = DashMap<ArrayVec<u8, 60>, usize, FxBuildHasher>;
+
+fn recursive_sum_dash(target: &[u8], basis_trie: &Trie<u8, usize>, cache: &DashCache) -> usize {
+    sum_with_memo(target, basis_trie, &mut Memo::new(cache))
+}
+
+fn sum_with_memo<S: Store<ArrayVec<u8, 60>, usize>>(
     target: &[u8],
     basis_trie: &Trie<u8, usize>,
-    cache: &DashMap<ArrayVec<u8, 60>, usize, FxBuildHasher>,
+    memo: &mut Memo<ArrayVec<u8, 60>, usize, S>,
 ) -> usize {
-    if let Some(cached) = cache.get(target) {
-        return *cached;
+    memo.call(target.try_into().unwrap(), &|memo, target| {
+        let mut sum = 0;
+        for (_, prefix_len) in basis_trie.common_prefix_search::<DropIter, _>(target) {
+            unsafe {
+                std::hint::assert_unchecked(*prefix_len <= target.len());
+            }
+            let new_target = &target[*prefix_len..];
+            if new_target.is_empty() {
+                sum += 1;
+            } else {
+                sum += sum_with_memo(new_target, basis_trie, memo);
+            }
+        }
+        sum
+    })
+}
+
+/// An Aho-Corasick automaton over the towel patterns, so walking a design through it once finds
+/// every pattern ending at each position.
+pub struct TowelAutomaton {
+    /// Bytes that appear in some pattern get their own class; class 0 is everything else.
+    classes: [u8; 256],
+    width: usize,
+    /// The full transition table, `width` entries per state, with state 0 as the root.
+    next: Vec<u32>,
+    /// The length of the pattern each state spells out, if it is one.
+    pattern_len: Vec<Option<u32>>,
+    /// The nearest proper suffix of each state that's a whole pattern, if any.
+    dictionary: Vec<Option<u32>>,
+}
+
+impl TowelAutomaton {
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let patterns: Vec<&[u8]> = patterns.into_iter().map(str::as_bytes).collect();
+        let mut classes = [0; 256];
+        let mut width = 1;
+        for &b in patterns.iter().copied().flatten() {
+            if classes[b as usize] == 0 {
+                classes[b as usize] = width as u8;
+                width += 1;
+            }
+        }
+
+        let mut next = vec![0; width];
+        let mut pattern_len = vec![None];
+        for pattern in patterns {
+            ensure!(!pattern.is_empty(), "Empty towel pattern");
+            let mut state = 0;
+            for &b in pattern {
+                let slot = state * width + classes[b as usize] as usize;
+                if next[slot] == 0 {
+                    next[slot] = pattern_len.len() as u32;
+                    next.extend(std::iter::repeat_n(0, width));
+                    pattern_len.push(None);
+                }
+                state = next[slot] as usize;
+            }
+            pattern_len[state] = Some(pattern.len() as u32);
+        }
+
+        // Breadth first, so every state's failure link is finished before its children need it.
+        let states = pattern_len.len();
+        let mut fail = vec![0; states];
+        let mut dictionary = vec![None; states];
+        let mut queue = VecDeque::from([0]);
+        while let Some(state) = queue.pop_front() {
+            for class in 1..width {
+                let slot = state * width + class;
+                let child = next[slot] as usize;
+                // Nothing ever goes back to the root, so 0 means there's no child yet.
+                let fallback = if state == 0 {
+                    0
+                } else {
+                    next[fail[state] * width + class]
+                };
+                if child == 0 {
+                    next[slot] = fallback;
+                    continue;
+                }
+                fail[child] = fallback as usize;
+                dictionary[child] = if pattern_len[fail[child]].is_some() {
+                    Some(fallback)
+                } else {
+                    dictionary[fail[child]]
+                };
+                queue.push_back(child);
+            }
+        }
+
+        Ok(TowelAutomaton {
+            classes,
+            width,
+            next,
+            pattern_len,
+            dictionary,
+        })
     }
-    let mut sum = 0;
-    for (_, prefix_len) in basis_trie.common_prefix_search::<DropIter, _>(target) {
-        unsafe {
-            std::hint::assert_unchecked(*prefix_len <= target.len());
+
+    /// Counts the ways to make `design` in a single pass, remembering which patterns can end each
+    /// prefix that can be made so the arrangements themselves can be read back out.
+    pub fn arrange<'a>(&self, design: &'a str) -> Arrangements<'a> {
+        let bytes = design.as_bytes();
+        let mut ways = vec![0_u128; bytes.len() + 1];
+        ways[0] = 1;
+        let mut ends = vec![Vec::new(); bytes.len() + 1];
+        let mut state = 0;
+        for (i, &b) in bytes.iter().enumerate() {
+            state = self.next[state * self.width + self.classes[b as usize] as usize] as usize;
+            let end = i + 1;
+            // A state that isn't a pattern itself can still have patterns among its suffixes.
+            let mut found = if self.pattern_len[state].is_some() {
+                Some(state as u32)
+            } else {
+                self.dictionary[state]
+            };
+            while let Some(pattern) = found {
+                let len = self.pattern_len[pattern as usize].unwrap();
+                let start = end - len as usize;
+                if ways[start] > 0 {
+                    ways[end] = ways[end].saturating_add(ways[start]);
+                    ends[end].push(len);
+                }
+                found = self.dictionary[pattern as usize];
+            }
         }
-        let new_target = &target[*prefix_len..];
-        if new_target.is_empty() {
-            sum += 1;
+        Arrangements { design, ways, ends }
+    }
+}
+
+/// The ways a single design can be made from the towel patterns.
+pub struct Arrangements<'a> {
+    design: &'a str,
+    /// How many ways there are to make each prefix, saturating rather than overflowing.
+    ways: Vec<u128>,
+    /// The lengths of the patterns that can finish each prefix, given that the rest of it can be
+    /// made too.
+    ends: Vec<Vec<u32>>,
+}
+
+impl<'a> Arrangements<'a> {
+    pub fn is_possible(&self) -> bool {
+        self.ways[self.design.len()] > 0
+    }
+
+    pub fn count(&self) -> Result<u128> {
+        let count = self.ways[self.design.len()];
+        ensure!(
+            count != u128::MAX,
+            "Too many ways to make {:?} to count",
+            self.design
+        );
+        Ok(count)
+    }
+
+    /// Some way of making the design, if there is one.
+    pub fn one(&self) -> Option<Vec<&'a str>> {
+        self.all().next()
+    }
+
+    /// Every way of making the design, generated lazily since there can be astronomically many.
+    /// Only prefixes that can be made are ever followed, so no work is wasted on dead ends.
+    pub fn all(&self) -> impl Iterator<Item = Vec<&'a str>> + '_ {
+        let end = self.design.len();
+        // Each entry is a position to carry on backwards from and which of its `ends` is next.
+        let mut stack = if self.is_possible() {
+            vec![(end, 0)]
         } else {
-            sum += recursive_sum_dash(new_target, basis_trie, cache);
+            Vec::new()
+        };
+        std::iter::from_fn(move || {
+            while let Some(&(position, choice)) = stack.last() {
+                if position == 0 {
+                    let mut towels: Vec<&'a str> = stack
+                        .windows(2)
+                        .map(|pair| &self.design[pair[1].0..pair[0].0])
+                        .collect();
+                    towels.reverse();
+                    stack.pop();
+                    return Some(towels);
+                }
+                let Some(&len) = self.ends[position].get(choice) else {
+                    stack.pop();
+                    continue;
+                };
+                stack.last_mut().unwrap().1 += 1;
+                stack.push((position - len as usize, 0));
+            }
+            None
+        })
+    }
+}
+
+#[aoc_generator(day19, part1, Automaton)]
+#[aoc_generator(day19, part2, Automaton)]
+fn gen_automaton(s: &str) -> Result<(TowelAutomaton, String)> {
+    let s = &*normalize_input(s);
+    let (basis, targets) = s.split_once("\n\n").context("No blank line")?;
+    Ok((TowelAutomaton::new(basis.split(", "))?, targets.to_owned()))
+}
+
+#[aoc(day19, part1, Automaton)]
+pub fn part1_automaton((automaton, targets): &(TowelAutomaton, String)) -> usize {
+    targets
+        .par_lines()
+        .filter(|design| automaton.arrange(design).is_possible())
+        .count()
+}
+
+#[aoc(day19, part2, Automaton)]
+pub fn part2_automaton((automaton, targets): &(TowelAutomaton, String)) -> Result<u128> {
+    targets
+        .par_lines()
+        .map(|design| automaton.arrange(design).count())
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static SITE_INPUT: &str = "r, wr, b, g, bwu, rb, gb, br
+
+brwrr
+bggr
+gbbr
+rrbgbr
+ubwu
+bwurrg
+brgr
+bbrwb
+";
+
+    #[test]
+    fn test_automaton() -> Result<()> {
+        let input = gen_automaton(SITE_INPUT)?;
+        assert_eq!(part1_automaton(&input), 6);
+        assert_eq!(part2_automaton(&input)?, 16);
+        assert_eq!(part1(&gen(SITE_INPUT)), 6);
+        assert_eq!(part2(&gen(SITE_INPUT)), 16);
+        assert_eq!(part2_dash(&gen(SITE_INPUT)), 16);
+
+        let (automaton, targets) = &input;
+        for (design, count) in targets.lines().zip([2, 1, 4, 6, 0, 1, 2, 0]) {
+            let arrangements = automaton.arrange(design);
+            assert_eq!(arrangements.count()?, count);
+            let all: Vec<_> = arrangements.all().collect();
+            assert_eq!(all.len() as u128, count);
+            assert_eq!(arrangements.one(), all.first().cloned());
+            for towels in all {
+                assert_eq!(towels.concat(), design);
+            }
         }
+        let mut gbbr: Vec<_> = automaton.arrange("gbbr").all().collect();
+        gbbr.sort();
+        assert_eq!(
+            gbbr,
+            vec![
+                vec!["g", "b", "b", "r"],
+                vec!["g", "b", "br"],
+                vec!["gb", "b", "r"],
+                vec!["gb", "br"],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_designs() -> Result<()> {
+        // Patterns that are suffixes of each other, so only the dictionary links find them all.
+        let automaton = TowelAutomaton::new(["a", "aa", "baa", "x"])?;
+        assert_eq!(automaton.arrange("aaaaaaaaaa").count()?, 89);
+        assert_eq!(automaton.arrange("baaa").count()?, 1);
+        assert!(!automaton.arrange("aaaza").is_possible());
+        let long = "a".repeat(150);
+        assert_eq!(
+            automaton.arrange(&long).count()?,
+            16130531424904581415797907386349
+        );
+        let longer = "a".repeat(100_000);
+        let arrangements = automaton.arrange(&longer);
+        assert!(arrangements.is_possible());
+        assert!(arrangements.count().is_err());
+        assert_eq!(arrangements.one().unwrap().concat(), longer);
+        assert!(TowelAutomaton::new(["a", ""]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_suffix_of_unfinished_pattern() -> Result<()> {
+        // After "ab" the automaton is partway through "abc", which isn't a pattern, but "b" still
+        // ends there.
+        let automaton = TowelAutomaton::new(["a", "b", "abc"])?;
+        assert_eq!(automaton.arrange("ab").count()?, 1);
+        assert_eq!(automaton.arrange("abcab").count()?, 1);
+        assert_eq!(automaton.arrange("abab").count()?, 1);
+        assert_eq!(
+            automaton.arrange("ab").all().collect::<Vec<_>>(),
+            vec![vec!["a", "b"]]
+        );
+        let automaton = TowelAutomaton::new(["r", "wr", "b", "g", "bwu", "rb", "gb", "br"])?;
+        assert_eq!(automaton.arrange("bwr").count()?, 1);
+        assert_eq!(automaton.arrange("gbwr").count()?, 2);
+        Ok(())
     }
-    cache.insert(unsafe { target.try_into().unwrap_unchecked() }, sum);
-    sum
 }
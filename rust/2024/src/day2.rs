@@ -13,6 +13,7 @@ use nom::{
 
 #[aoc(day2, part1, Naive)]
 pub fn part1_naive(input: &str) -> usize {
+    let input = &*normalize_input(input);
     scope!("part1");
     input
         .lines()
@@ -36,6 +37,7 @@ pub fn part1_naive(input: &str) -> usize {
 
 #[aoc(day2, part1, Opt)]
 pub fn part1_opt(input: &str) -> usize {
+    let input = &*normalize_input(input);
     scope!("part1");
     input
         .lines()
@@ -73,6 +75,7 @@ pub fn part1_opt(input: &str) -> usize {
 
 #[aoc(day2, part1, Hyperopt)]
 pub fn part1(input: &str) -> usize {
+    let input = &*normalize_input(input);
     let mut count = 0;
     let mut bytes = input.as_bytes();
     while bytes.len() > 1 {
@@ -144,6 +147,7 @@ fn check_line(input: &[u8]) -> (bool, &[u8]) {
 
 #[aoc(day2, part2, Naive)]
 pub fn part2_naive(input: &str) -> usize {
+    let input = &*normalize_input(input);
     scope!("part2");
     input.lines().filter(|&line| naive_filter_fn(line)).count()
 }
@@ -166,6 +170,7 @@ fn naive_filter_fn(line: &str) -> bool {
 
 #[aoc(day2, part2, Opt)]
 pub fn part2_opt(input: &str) -> usize {
+    let input = &*normalize_input(input);
     scope!("part2");
     input.lines().filter(|&line| opt_filter_fn(line)).count()
 }
@@ -344,6 +349,7 @@ fn check_legal_range(x: Option<i16>) -> bool {
 
 #[aoc(day2, part2, Hyperopt)]
 pub fn part2(input: &str) -> usize {
+    let input = &*normalize_input(input);
     let mut count = 0;
     let mut bytes = input.as_bytes();
     while bytes.len() > 1 {
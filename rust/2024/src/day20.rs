@@ -1,10 +1,13 @@
 use super::*;
 
+use anyhow::{bail, ensure};
 use rayon::prelude::*;
 use rustc_hash::FxHashMap;
+use std::collections::{BTreeMap, VecDeque};
 
 #[aoc(day20, part1)]
 pub fn part1(s: &str) -> usize {
+    let s = &*normalize_input(s);
     let columns = s.find("\n").unwrap();
     let stride = columns + 1;
     let rows = (s.len() + 1) / stride;
@@ -69,6 +72,7 @@ pub fn part1(s: &str) -> usize {
 
 #[aoc(day20, part2)]
 pub fn part2(s: &str) -> usize {
+    let s = &*normalize_input(s);
     let columns = s.find("\n").unwrap();
     let stride = columns + 1;
     let rows = (s.len() + 1) / stride;
@@ -130,3 +134,279 @@ pub fn part2(s: &str) -> usize {
         })
         .sum()
 }
+
+/// A racetrack of any shape, with how far every cell is from the start and from the end.
+pub struct Racetrack {
+    rows: usize,
+    columns: usize,
+    from_start: Vec<u32>,
+    to_end: Vec<u32>,
+}
+
+impl Racetrack {
+    pub fn parse(s: &str) -> Result<Self> {
+        let columns = s.find('\n').unwrap_or(s.len());
+        let mut open = Vec::new();
+        let (mut start, mut end) = (None, None);
+        for line in s.lines() {
+            ensure!(line.len() == columns, "Ragged line {line:?}");
+            for b in line.bytes() {
+                match b {
+                    b'S' => start = Some(open.len()),
+                    b'E' => end = Some(open.len()),
+                    b'.' | b'#' => (),
+                    _ => bail!("Unexpected byte {:?}", b as char),
+                }
+                open.push(b != b'#');
+            }
+        }
+        let rows = open.len() / columns.max(1);
+        let mut track = Racetrack {
+            rows,
+            columns,
+            from_start: Vec::new(),
+            to_end: Vec::new(),
+        };
+        track.from_start = track.distances(&open, start.context("No start")?);
+        track.to_end = track.distances(&open, end.context("No end")?);
+        ensure!(
+            track.best_time().is_some(),
+            "The end can't be reached without cheating"
+        );
+        Ok(track)
+    }
+
+    fn distances(&self, open: &[bool], origin: usize) -> Vec<u32> {
+        let mut distance = vec![u32::MAX; open.len()];
+        distance[origin] = 0;
+        let mut queue = VecDeque::from([origin]);
+        while let Some(cell) = queue.pop_front() {
+            let (row, column) = (cell / self.columns, cell % self.columns);
+            let neighbours = [
+                (row > 0).then(|| cell - self.columns),
+                (row + 1 < self.rows).then(|| cell + self.columns),
+                (column > 0).then(|| cell - 1),
+                (column + 1 < self.columns).then(|| cell + 1),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if open[next] && distance[next] == u32::MAX {
+                    distance[next] = distance[cell] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        distance
+    }
+
+    /// The fastest time without cheating, if the end can be reached at all.
+    pub fn best_time(&self) -> Option<u32> {
+        let end = self.to_end.iter().position(|&d| d == 0)?;
+        Some(self.from_start[end]).filter(|&d| d != u32::MAX)
+    }
+
+    /// How many distinct cheats of up to `max_cheat` picoseconds save each amount of time, for
+    /// savings of at least `min_saving`. A cheat is identified by where it starts and ends, and
+    /// can start and end on any open cell reachable from the start and from which the end can be
+    /// reached.
+    pub fn cheat_savings(&self, max_cheat: usize, min_saving: u32) -> BTreeMap<u32, usize> {
+        let Some(best) = self.best_time() else {
+            return BTreeMap::new();
+        };
+        let min_saving = min_saving.max(1);
+        let reach = max_cheat as isize;
+        // Every offset in the diamond, by row so each row's run of columns can be clipped to the
+        // map at once.
+        let diamond: Vec<(isize, isize)> =
+            (-reach..=reach).map(|dr| (dr, reach - dr.abs())).collect();
+        let histogram = (0..self.rows)
+            .into_par_iter()
+            .map(|row| {
+                let mut histogram = vec![0_usize; best as usize + 1];
+                for column in 0..self.columns {
+                    let cell = row * self.columns + column;
+                    let from_start = self.from_start[cell];
+                    if from_start == u32::MAX || from_start + min_saving > best {
+                        continue;
+                    }
+                    for &(dr, width) in &diamond {
+                        let Some(end_row) = row.checked_add_signed(dr).filter(|&r| r < self.rows)
+                        else {
+                            continue;
+                        };
+                        let first = column.saturating_sub(width as usize);
+                        let last = (column + width as usize).min(self.columns - 1);
+                        for end_column in first..=last {
+                            let to_end = self.to_end[end_row * self.columns + end_column];
+                            if to_end == u32::MAX {
+                                continue;
+                            }
+                            let cheat = dr.unsigned_abs() + column.abs_diff(end_column);
+                            let time = from_start + cheat as u32 + to_end;
+                            if time + min_saving <= best {
+                                histogram[(best - time) as usize] += 1;
+                            }
+                        }
+                    }
+                }
+                histogram
+            })
+            .reduce(
+                || vec![0; best as usize + 1],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    a
+                },
+            );
+        histogram
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, count)| count > 0)
+            .map(|(saving, count)| (saving as u32, count))
+            .collect()
+    }
+
+    pub fn count_cheats(&self, max_cheat: usize, min_saving: u32) -> usize {
+        self.cheat_savings(max_cheat, min_saving).values().sum()
+    }
+}
+
+#[aoc(day20, part1, Bfs)]
+pub fn part1_bfs(s: &str) -> Result<usize> {
+    let s = &*normalize_input(s);
+    Ok(Racetrack::parse(s)?.count_cheats(2, 100))
+}
+
+#[aoc(day20, part2, Bfs)]
+pub fn part2_bfs(s: &str) -> Result<usize> {
+    let s = &*normalize_input(s);
+    Ok(Racetrack::parse(s)?.count_cheats(20, 100))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static SITE_INPUT: &str = "###############
+#...#...#.....#
+#.#.#.#.#.###.#
+#S#...#.#.#...#
+#######.#.#.###
+#######.#.#...#
+#######.#.###.#
+###..E#...#...#
+###.#######.###
+#...###...#...#
+#.#####.#.###.#
+#.#...#.#.#...#
+#.#.#.#.#.#.###
+#...#...#...###
+###############
+";
+
+    #[test]
+    fn test_cheat_savings() -> Result<()> {
+        let track = Racetrack::parse(SITE_INPUT)?;
+        assert_eq!(track.best_time(), Some(84));
+        assert_eq!(
+            track.cheat_savings(2, 1),
+            BTreeMap::from([
+                (2, 14),
+                (4, 14),
+                (6, 2),
+                (8, 4),
+                (10, 2),
+                (12, 3),
+                (20, 1),
+                (36, 1),
+                (38, 1),
+                (40, 1),
+                (64, 1),
+            ])
+        );
+        assert_eq!(
+            track.cheat_savings(20, 50),
+            BTreeMap::from([
+                (50, 32),
+                (52, 31),
+                (54, 29),
+                (56, 39),
+                (58, 25),
+                (60, 23),
+                (62, 20),
+                (64, 19),
+                (66, 12),
+                (68, 14),
+                (70, 12),
+                (72, 22),
+                (74, 4),
+                (76, 3),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_branching_tracks() -> Result<()> {
+        // Nothing beats walking straight across an empty room.
+        let room = Racetrack::parse("S....\n.....\n....E\n")?;
+        assert_eq!(room.best_time(), Some(6));
+        assert!(room.cheat_savings(20, 1).is_empty());
+
+        // Two ways round, with a dead end off the long one.
+        let track = Racetrack::parse("#######\n#S...E#\n#.###.#\n#.....#\n##.####\n")?;
+        assert_eq!(track.best_time(), Some(4));
+        assert!(track.cheat_savings(2, 1).is_empty());
+        let track = Racetrack::parse("#######\n#S#...#\n#.#.#.#\n#...#E#\n#######\n")?;
+        assert_eq!(track.best_time(), Some(10));
+        assert_eq!(track.cheat_savings(2, 1), BTreeMap::from([(2, 2), (4, 2)]));
+
+        assert!(Racetrack::parse("S#E\n").is_err());
+        assert!(Racetrack::parse("S..\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_every_pair() -> Result<()> {
+        let mut next = xorshift(0x2545_f491_4f6c_dd1d_u64);
+        let mut tried = 0;
+        while tried < 20 {
+            let (rows, columns) = (5 + next(12) as usize, 5 + next(12) as usize);
+            let mut map = String::new();
+            for row in 0..rows {
+                for column in 0..columns {
+                    map.push(match (row, column) {
+                        (0, 0) => 'S',
+                        _ if (row, column) == (rows - 1, columns - 1) => 'E',
+                        _ if next(3) == 0 => '#',
+                        _ => '.',
+                    });
+                }
+                map.push('\n');
+            }
+            let Ok(track) = Racetrack::parse(&map) else {
+                continue;
+            };
+            tried += 1;
+            let best = track.best_time().unwrap();
+            for max_cheat in [2, 3, 6] {
+                let mut expected = BTreeMap::new();
+                for from in 0..rows * columns {
+                    for to in 0..rows * columns {
+                        let (a, b) = (track.from_start[from], track.to_end[to]);
+                        let cheat = (from / columns).abs_diff(to / columns)
+                            + (from % columns).abs_diff(to % columns);
+                        if a == u32::MAX || b == u32::MAX || cheat > max_cheat {
+                            continue;
+                        }
+                        let time = a + b + cheat as u32;
+                        if time < best {
+                            *expected.entry(best - time).or_insert(0) += 1;
+                        }
+                    }
+                }
+                assert_eq!(track.cheat_savings(max_cheat, 1), expected, "{map}");
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
 use super::*;
+use crate::memo::{DenseStore, Memo};
+use anyhow::{bail, ensure};
 use arrayvec::ArrayVec;
-use std::cmp::Ordering;
-use std::collections::VecDeque;
+use rustc_hash::FxHashMap;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
 use std::fmt::Debug;
 use std::ops::{Index, IndexMut};
 
@@ -80,7 +83,8 @@ impl DirectionPad {
 
 #[aoc(day21, part1)]
 pub fn part1(s: &str) -> u64 {
-    let mut cache = CacheType::default();
+    let s = &*normalize_input(s);
+    let mut cache = new_cache();
     s.lines().map(|line| part1_per_line(line, &mut cache)).sum()
 }
 
@@ -100,6 +104,7 @@ fn part1_per_line(line: &str, cache: &mut CacheType) -> u64 {
 
 #[aoc(day21, part1, Pregen)]
 pub fn part1_pregen(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     const PREGEN: [[u64; 5]; 5] = [
         [1, 5, 7, 4, 8],
         [9, 1, 9, 8, 4],
@@ -127,7 +132,8 @@ pub fn part1_pregen(s: &str) -> u64 {
 
 #[aoc(day21, part2)]
 pub fn part2(s: &str) -> u64 {
-    let mut cache = CacheType::default();
+    let s = &*normalize_input(s);
+    let mut cache = new_cache();
     s.lines()
         .map(|line| {
             let bytes = line.as_bytes();
@@ -148,6 +154,7 @@ pub fn part2(s: &str) -> u64 {
 
 #[aoc(day21, part2, Pregen)]
 pub fn part2_pregen(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     const PREGEN: [[u64; 5]; 5] = [
         [1, 5743602247, 10218188221, 5743602246, 10218188222],
         [9009012839, 1, 11317884431, 9009012838, 5930403600],
@@ -175,6 +182,7 @@ pub fn part2_pregen(s: &str) -> u64 {
 
 #[aoc(day21, part1, UltraPreGen)]
 pub fn part1_ultra(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     const PREGEN: [[u64; 11]; 11] = [
         [1, 25, 12, 19, 26, 13, 20, 27, 14, 21, 10],
         [21, 1, 10, 11, 12, 19, 20, 13, 20, 21, 22],
@@ -209,6 +217,7 @@ pub fn part1_ultra(s: &str) -> u64 {
 
 #[aoc(day21, part2, UltraPreGen)]
 pub fn part2_ultra(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     const PREGEN: [[u64; 11]; 11] = [
         [
             1,
@@ -373,7 +382,16 @@ pub fn part2_ultra(s: &str) -> u64 {
         .sum()
 }
 
-type CacheType = [[[Option<u64>; 26]; 5]; 5];
+/// A move between two direction buttons, and how many layers of robots are typing it.
+type DirpadMove = (DirectionPad, DirectionPad, u8);
+
+type CacheType = Memo<DirpadMove, u64, DenseStore<u64, fn(&DirpadMove) -> usize>>;
+
+fn new_cache() -> CacheType {
+    Memo::new(DenseStore::new(5 * 5 * 26, |&(start, end, layer)| {
+        (start as usize * 5 + end as usize) * 26 + layer as usize
+    }))
+}
 
 // Gets the cheapest cost of moving from start to end on the 10-key
 fn cheapest_10key_path(start: u8, end: u8, total_layers: u8, cache: &mut CacheType) -> u64 {
@@ -500,70 +518,313 @@ fn cheapest_dirpad_path(
     layer: u8,
     cache: &mut CacheType,
 ) -> u64 {
-    unsafe {
-        std::hint::assert_unchecked(layer <= 25);
+    cache.call((start, end, layer), &|cache, &(start, end, layer)| {
+        unsafe {
+            std::hint::assert_unchecked(layer <= 25);
+        }
+        if layer == 0 {
+            return 1;
+        }
+        let start_coord = start.to_coord();
+        let end_coord = end.to_coord();
+        let mut bfs = VecDeque::new();
+        let mut initial_dir = ArrayVec::<DirectionPad, 5>::new();
+        initial_dir.push(DirectionPad::A);
+        bfs.push_back((start_coord, initial_dir));
+        let mut res = u64::MAX;
+
+        while let Some((coord, mut directions)) = bfs.pop_front() {
+            if coord == end_coord {
+                unsafe { directions.push_unchecked(DirectionPad::A) };
+                res = res.min(
+                    directions
+                        .windows(2)
+                        .map(|window| cheapest_dirpad_path(window[0], window[1], layer - 1, cache))
+                        .sum(),
+                );
+                continue;
+            }
+            if coord == (1, 0) {
+                continue;
+            }
+
+            match coord.0.cmp(&end_coord.0) {
+                Ordering::Less => {
+                    let mut dir_clone = directions.clone();
+                    unsafe { dir_clone.push_unchecked(DirectionPad::Up) };
+                    bfs.push_back(((coord.0 + 1, coord.1), dir_clone));
+                }
+                Ordering::Greater => {
+                    let mut dir_clone = directions.clone();
+                    unsafe { dir_clone.push_unchecked(DirectionPad::Down) };
+                    bfs.push_back(((coord.0 - 1, coord.1), dir_clone));
+                }
+                Ordering::Equal => (),
+            }
+
+            match coord.1.cmp(&end_coord.1) {
+                Ordering::Less => {
+                    unsafe { directions.push_unchecked(DirectionPad::Right) };
+                    bfs.push_back(((coord.0, coord.1 + 1), directions));
+                }
+                Ordering::Greater => {
+                    unsafe { directions.push_unchecked(DirectionPad::Left) };
+                    bfs.push_back(((coord.0, coord.1 - 1), directions));
+                }
+                Ordering::Equal => (),
+            }
+        }
+        debug!(start);
+        debug!(end);
+        debug!(layer);
+        debug!(res);
+        res
+    })
+}
+
+/// A keypad read from a grid of button labels, where spaces are gaps the arm pointing at it must
+/// never pass over.
+#[derive(Clone, Debug)]
+pub struct Keypad {
+    buttons: Vec<u8>,
+    positions: Vec<(usize, usize)>,
+    at: FxHashMap<(usize, usize), usize>,
+}
+
+impl Keypad {
+    pub fn from_grid(grid: &str) -> Result<Self> {
+        let mut keypad = Keypad {
+            buttons: Vec::new(),
+            positions: Vec::new(),
+            at: FxHashMap::default(),
+        };
+        for (row, line) in grid.lines().enumerate() {
+            for (col, button) in line.bytes().enumerate() {
+                if button == b' ' {
+                    continue;
+                }
+                ensure!(
+                    !keypad.buttons.contains(&button),
+                    "Button {:?} appears twice",
+                    button as char
+                );
+                keypad.at.insert((row, col), keypad.buttons.len());
+                keypad.buttons.push(button);
+                keypad.positions.push((row, col));
+            }
+        }
+        ensure!(keypad.index(b'A').is_some(), "Keypad has no A button");
+        Ok(keypad)
     }
-    if let Some(cost) = cache[start][end][layer as usize] {
-        return cost;
+
+    pub fn numeric() -> Self {
+        Self::from_grid("789\n456\n123\n 0A").unwrap()
     }
-    if layer == 0 {
-        return 1;
+
+    pub fn directional() -> Self {
+        Self::from_grid(" ^A\n<v>").unwrap()
     }
-    let start_coord = start.to_coord();
-    let end_coord = end.to_coord();
-    let mut bfs = VecDeque::new();
-    let mut initial_dir = ArrayVec::<DirectionPad, 5>::new();
-    initial_dir.push(DirectionPad::A);
-    bfs.push_back((start_coord, initial_dir));
-    let mut res = u64::MAX;
 
-    while let Some((coord, mut directions)) = bfs.pop_front() {
-        if coord == end_coord {
-            unsafe { directions.push_unchecked(DirectionPad::A) };
-            res = res.min(
-                directions
-                    .windows(2)
-                    .map(|window| cheapest_dirpad_path(window[0], window[1], layer - 1, cache))
-                    .sum(),
-            );
-            continue;
-        }
-        if coord == (1, 0) {
-            continue;
+    fn index(&self, button: u8) -> Option<usize> {
+        self.buttons.iter().position(|&b| b == button)
+    }
+
+    /// The button next to `button` in the direction `arrow` points, if there is one.
+    fn neighbour(&self, button: usize, arrow: u8) -> Option<usize> {
+        let (row, col) = self.positions[button];
+        let next = match arrow {
+            b'^' => (row.checked_sub(1)?, col),
+            b'v' => (row + 1, col),
+            b'<' => (row, col.checked_sub(1)?),
+            b'>' => (row, col + 1),
+            _ => return None,
+        };
+        self.at.get(&next).copied()
+    }
+}
+
+/// Something for every pair of buttons on a keypad, indexed by where the arm starts then ends.
+type ButtonTable<T> = Vec<Vec<T>>;
+
+/// A chain of keypads where each arm is steered by a robot typing on the next keypad along, and the
+/// last keypad is typed on directly. Every arm starts, and ends up back, on its keypad's `A`.
+pub struct KeypadChain {
+    pads: Vec<Keypad>,
+    /// `costs[i][a][b]` is how many buttons have to be pressed on the last keypad to move the arm
+    /// over keypad `i` from button `a` to button `b` and press it.
+    costs: Vec<ButtonTable<u128>>,
+    /// `moves[i][a][b]` is a cheapest sequence of buttons on keypad `i + 1` that does that.
+    moves: Vec<ButtonTable<Vec<u8>>>,
+}
+
+impl KeypadChain {
+    /// A `target` keypad, steered through `robots` directional keypads operated by robots, the last
+    /// of which is steered by a directional keypad the human types on.
+    pub fn new(target: Keypad, directional: Keypad, robots: usize) -> Result<Self> {
+        let mut pads = vec![target];
+        pads.extend(std::iter::repeat_n(directional, robots + 1));
+        Self::from_pads(pads)
+    }
+
+    /// Keypads in order from the one being typed on by the end of the chain to the one the human
+    /// types on. Every keypad but the first has to have all four arrows.
+    pub fn from_pads(pads: Vec<Keypad>) -> Result<Self> {
+        ensure!(!pads.is_empty(), "There has to be at least one keypad");
+        let human = pads.last().unwrap();
+        let mut costs = vec![vec![vec![1; human.buttons.len()]; human.buttons.len()]];
+        let mut moves = Vec::new();
+        for pair in pads.windows(2).rev() {
+            let (cost, best) = Self::cheapest_moves(&pair[0], &pair[1], costs.last().unwrap())?;
+            costs.push(cost);
+            moves.push(best);
         }
+        costs.reverse();
+        moves.reverse();
+        Ok(KeypadChain { pads, costs, moves })
+    }
 
-        match coord.0.cmp(&end_coord.0) {
-            Ordering::Less => {
-                let mut dir_clone = directions.clone();
-                unsafe { dir_clone.push_unchecked(DirectionPad::Up) };
-                bfs.push_back(((coord.0 + 1, coord.1), dir_clone));
+    /// Dijkstra over the arm's button on `pad` paired with the last button pressed on `controller`,
+    /// which is where that arm is left pointing, given what pressing each of `controller`'s buttons
+    /// costs.
+    fn cheapest_moves(
+        pad: &Keypad,
+        controller: &Keypad,
+        controller_costs: &[Vec<u128>],
+    ) -> Result<(ButtonTable<u128>, ButtonTable<Vec<u8>>)> {
+        let arrows = b"^v<>"
+            .iter()
+            .map(|&arrow| {
+                controller
+                    .index(arrow)
+                    .map(|index| (arrow, index))
+                    .with_context(|| format!("Keypad has no {:?} button", arrow as char))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let press = controller.index(b'A').unwrap();
+        let states = pad.buttons.len() * controller.buttons.len();
+        let state = |button: usize, last: usize| button * controller.buttons.len() + last;
+
+        let mut costs = vec![vec![u128::MAX; pad.buttons.len()]; pad.buttons.len()];
+        let mut moves = vec![vec![Vec::new(); pad.buttons.len()]; pad.buttons.len()];
+        for from in 0..pad.buttons.len() {
+            let mut distance = vec![u128::MAX; states];
+            let mut previous = vec![usize::MAX; states];
+            let mut heap = BinaryHeap::new();
+            distance[state(from, press)] = 0;
+            heap.push(Reverse((0, from, press)));
+            while let Some(Reverse((cost, button, last))) = heap.pop() {
+                if cost > distance[state(button, last)] {
+                    continue;
+                }
+                for &(arrow, index) in &arrows {
+                    let Some(next) = pad.neighbour(button, arrow) else {
+                        continue;
+                    };
+                    let next_cost = cost
+                        .checked_add(controller_costs[last][index])
+                        .context("Press count overflows a u128")?;
+                    if next_cost < distance[state(next, index)] {
+                        distance[state(next, index)] = next_cost;
+                        previous[state(next, index)] = state(button, last);
+                        heap.push(Reverse((next_cost, next, index)));
+                    }
+                }
             }
-            Ordering::Greater => {
-                let mut dir_clone = directions.clone();
-                unsafe { dir_clone.push_unchecked(DirectionPad::Down) };
-                bfs.push_back(((coord.0 - 1, coord.1), dir_clone));
+
+            for to in 0..pad.buttons.len() {
+                let Some((total, last)) = (0..controller.buttons.len())
+                    .filter(|&last| distance[state(to, last)] != u128::MAX)
+                    .map(|last| {
+                        let total = distance[state(to, last)]
+                            .checked_add(controller_costs[last][press])
+                            .context("Press count overflows a u128")?;
+                        Ok((total, last))
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .into_iter()
+                    .min()
+                else {
+                    bail!(
+                        "Can't get from {:?} to {:?}",
+                        pad.buttons[from] as char,
+                        pad.buttons[to] as char
+                    );
+                };
+                let mut sequence = vec![b'A'];
+                let mut current = state(to, last);
+                while current != state(from, press) {
+                    sequence.push(controller.buttons[current % controller.buttons.len()]);
+                    current = previous[current];
+                }
+                sequence.reverse();
+                costs[from][to] = total;
+                moves[from][to] = sequence;
             }
-            Ordering::Equal => (),
         }
+        Ok((costs, moves))
+    }
 
-        match coord.1.cmp(&end_coord.1) {
-            Ordering::Less => {
-                unsafe { directions.push_unchecked(DirectionPad::Right) };
-                bfs.push_back(((coord.0, coord.1 + 1), directions));
-            }
-            Ordering::Greater => {
-                unsafe { directions.push_unchecked(DirectionPad::Left) };
-                bfs.push_back(((coord.0, coord.1 - 1), directions));
+    /// How many buttons the human has to press for the first keypad to type `code`.
+    pub fn presses(&self, code: &str) -> Result<u128> {
+        let mut last = self.pads[0].index(b'A').unwrap();
+        let mut total = 0_u128;
+        for button in code.bytes() {
+            let next = self.pads[0]
+                .index(button)
+                .with_context(|| format!("Keypad has no {:?} button", button as char))?;
+            total = total
+                .checked_add(self.costs[0][last][next])
+                .context("Press count overflows a u128")?;
+            last = next;
+        }
+        Ok(total)
+    }
+
+    /// A cheapest sequence of buttons for the human to press to type `code`, which has to be short
+    /// enough to actually build.
+    pub fn sequence(&self, code: &str) -> Result<String> {
+        const MAX_LEN: u128 = 1 << 24;
+        let len = self.presses(code)?;
+        ensure!(len <= MAX_LEN, "The sequence is {len} presses long");
+        let mut sequence = code.as_bytes().to_vec();
+        for (pad, moves) in self.pads.iter().zip(&self.moves) {
+            let mut last = pad.index(b'A').unwrap();
+            let mut expanded = Vec::with_capacity(len as usize);
+            for &button in &sequence {
+                let next = pad.index(button).unwrap();
+                expanded.extend_from_slice(&moves[last][next]);
+                last = next;
             }
-            Ordering::Equal => (),
+            sequence = expanded;
         }
+        Ok(String::from_utf8(sequence)?)
     }
-    cache[start][end][layer as usize] = Some(res);
-    debug!(start);
-    debug!(end);
-    debug!(layer);
-    debug!(res);
-    res
+}
+
+/// The sum of each code's press count multiplied by its numeric part.
+fn chain_complexity(s: &str, robots: usize) -> Result<u128> {
+    let chain = KeypadChain::new(Keypad::numeric(), Keypad::directional(), robots)?;
+    s.lines()
+        .map(|code| {
+            let numeric_part: u128 = code
+                .trim_end_matches('A')
+                .parse()
+                .with_context(|| format!("Bad code {code:?}"))?;
+            Ok(chain.presses(code)? * numeric_part)
+        })
+        .sum()
+}
+
+#[aoc(day21, part1, Chain)]
+pub fn part1_chain(s: &str) -> Result<u128> {
+    let s = &*normalize_input(s);
+    chain_complexity(s, 2)
+}
+
+#[aoc(day21, part2, Chain)]
+pub fn part2_chain(s: &str) -> Result<u128> {
+    let s = &*normalize_input(s);
+    chain_complexity(s, 25)
 }
 
 #[cfg(test)]
@@ -594,7 +855,7 @@ mod test {
 
     #[test]
     fn test_per_line() {
-        let mut cache = CacheType::default();
+        let mut cache = new_cache();
         let lines: Vec<_> = SITE_INPUT.lines().collect();
         assert_eq!(part1_per_line(lines[0], &mut cache), 68 * 29, "line 0");
         assert_eq!(part1_per_line(lines[1], &mut cache), 60 * 980, "line 1");
@@ -603,9 +864,79 @@ mod test {
         assert_eq!(part1_per_line(lines[4], &mut cache), 64 * 379, "line 4");
     }
 
+    /// Presses `sequence` on the last keypad of `chain` and returns what comes out of the first,
+    /// panicking if any arm passes over a gap.
+    fn type_through(chain: &KeypadChain, sequence: &str) -> String {
+        let mut arms: Vec<usize> = chain
+            .pads
+            .iter()
+            .map(|pad| pad.index(b'A').unwrap())
+            .collect();
+        let mut typed = String::new();
+        'presses: for button in sequence.bytes() {
+            // Work down from the human's keypad until a press moves an arm instead.
+            let mut pressed = button;
+            for level in (0..chain.pads.len() - 1).rev() {
+                if pressed != b'A' {
+                    arms[level] = chain.pads[level]
+                        .neighbour(arms[level], pressed)
+                        .expect("arm moved over a gap");
+                    continue 'presses;
+                }
+                pressed = chain.pads[level].buttons[arms[level]];
+            }
+            typed.push(pressed as char);
+        }
+        typed
+    }
+
+    #[test]
+    fn test_chain() -> Result<()> {
+        assert_eq!(part1_chain(SITE_INPUT)?, 126384);
+        assert_eq!(part2_chain(SITE_INPUT)?, part2(SITE_INPUT) as u128);
+        let chain = KeypadChain::new(Keypad::numeric(), Keypad::directional(), 2)?;
+        for (code, presses) in SITE_INPUT.lines().zip([68, 60, 68, 64, 64]) {
+            assert_eq!(chain.presses(code)?, presses);
+            let sequence = chain.sequence(code)?;
+            assert_eq!(sequence.len(), presses as usize);
+            assert_eq!(type_through(&chain, &sequence), code);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_layouts() -> Result<()> {
+        // Getting between the top corners means going all the way round the gap.
+        let target = Keypad::from_grid("1 2\n345\n  A")?;
+        let chain = KeypadChain::new(target.clone(), Keypad::directional(), 1)?;
+        for code in ["12A", "2121A", "A5A"] {
+            let sequence = chain.sequence(code)?;
+            assert_eq!(sequence.len() as u128, chain.presses(code)?);
+            assert_eq!(type_through(&chain, &sequence), code);
+        }
+        // A differently laid out directional keypad for the human.
+        let sideways = Keypad::from_grid("^v\n<>\n A")?;
+        let chain = KeypadChain::from_pads(vec![target, Keypad::directional(), sideways])?;
+        let sequence = chain.sequence("12A")?;
+        assert_eq!(type_through(&chain, &sequence), "12A");
+
+        // Long chains need more than a u64, until they need more than a u128 too.
+        let chain = KeypadChain::new(Keypad::numeric(), Keypad::directional(), 60)?;
+        assert!(chain.presses("029A")? > u64::MAX as u128);
+        assert!(chain.sequence("029A").is_err());
+        assert!(KeypadChain::new(Keypad::numeric(), Keypad::directional(), 120).is_err());
+
+        assert!(Keypad::from_grid("12\n21A").is_err());
+        assert!(Keypad::from_grid("12").is_err());
+        assert!(KeypadChain::from_pads(vec![Keypad::directional(), Keypad::numeric()]).is_err());
+        let island = Keypad::from_grid("1 A")?;
+        assert!(KeypadChain::new(island, Keypad::directional(), 1).is_err());
+        Ok(())
+    }
+
     #[test]
     fn pregen_2_deep() {
-        let mut cache = CacheType::default();
+        let mut cache = new_cache();
         let mut final_result = [[0_u64; 5]; 5];
         for start in ALL_DIRPADS {
             for end in ALL_DIRPADS {
@@ -617,7 +948,7 @@ mod test {
 
     #[test]
     fn pregen_25_deep() {
-        let mut cache = CacheType::default();
+        let mut cache = new_cache();
         let mut final_result = [[0_u64; 5]; 5];
         for start in ALL_DIRPADS {
             for end in ALL_DIRPADS {
@@ -8,6 +8,7 @@ use rustc_hash::{FxBuildHasher, FxHashMap, FxHashSet};
 
 #[aoc(day22, part1)]
 pub fn part1(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     s.par_lines()
         .map(|line| line.parse::<u64>().unwrap())
         .map(|initial_secret| (0..2000).fold(initial_secret, |secret, _| evolve(secret)))
@@ -23,6 +24,7 @@ fn evolve(mut secret: u64) -> u64 {
 
 #[aoc(day22, part2)]
 pub fn part2(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     let total_profits = Mutex::new(FxHashMap::<[i8; 4], u64>::default());
     s.par_lines()
         .map(|line| line.parse::<u64>().unwrap())
@@ -75,6 +77,7 @@ pub fn part2(s: &str) -> u64 {
 
 #[aoc(day22, part2, Scope)]
 pub fn part2_scope(input: &str) -> u64 {
+    let input = &*normalize_input(input);
     let mut total_profits = FxHashMap::<[i8; 4], u64>::default();
 
     rayon::scope(|s| {
@@ -138,6 +141,7 @@ pub fn part2_scope(input: &str) -> u64 {
 
 #[aoc(day22, part2, DashMap)]
 pub fn part2_dash(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     let total_profits = DashMap::<[i8; 4], u64, _>::with_hasher(FxBuildHasher);
 
     s.par_lines()
@@ -181,3 +185,181 @@ pub fn part2_dash(s: &str) -> u64 {
 
     total_profits.into_iter().map(|(_, v)| v).max().unwrap()
 }
+
+/// Every window of four price changes, each in `-9..=9`, as a base 19 number.
+const WINDOWS: usize = 19 * 19 * 19 * 19;
+
+/// Few enough buyers that the bananas for any one window fit in a `u16`, and that buyers can be
+/// stamped with a `u16` too, before a [`Tally`] has to flush them into its totals.
+const BUYERS_PER_FLUSH: u16 = u16::MAX / 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BestSequence {
+    pub changes: [i8; 4],
+    pub bananas: u64,
+}
+
+fn window_changes(mut window: usize) -> [i8; 4] {
+    let mut changes = [0; 4];
+    for change in changes.iter_mut().rev() {
+        *change = (window % 19) as i8 - 9;
+        window /= 19;
+    }
+    changes
+}
+
+/// One thread's running count of what selling at the first sight of each window would make. Most
+/// of the counting is in narrow arrays that stay in cache, flushed into the totals now and then.
+struct Tally {
+    totals: Vec<u64>,
+    bananas: Vec<u16>,
+    last_seen: Vec<u16>,
+    buyers: u16,
+}
+
+impl Tally {
+    fn new() -> Self {
+        Tally {
+            totals: vec![0; WINDOWS],
+            bananas: vec![0; WINDOWS],
+            last_seen: vec![0; WINDOWS],
+            buyers: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        self.totals
+            .iter_mut()
+            .zip(&self.bananas)
+            .for_each(|(total, &bananas)| *total += bananas as u64);
+        self.bananas.fill(0);
+        self.last_seen.fill(0);
+        self.buyers = 0;
+    }
+
+    fn add_buyer(mut self, mut secret: u64) -> Self {
+        if self.buyers == BUYERS_PER_FLUSH {
+            self.flush();
+        }
+        // Stamps start at 1 so that 0 can mean never seen.
+        self.buyers += 1;
+        let stamp = self.buyers;
+        let mut price = (secret % 10) as usize;
+        let mut window = 0;
+        for step in 1..=2000 {
+            secret = evolve(secret);
+            let next_price = (secret % 10) as usize;
+            window = (window * 19 + next_price + 9 - price) % WINDOWS;
+            price = next_price;
+            if step >= 4 && self.last_seen[window] != stamp {
+                self.last_seen[window] = stamp;
+                self.bananas[window] += price as u16;
+            }
+        }
+        self
+    }
+
+    fn into_totals(mut self) -> Vec<u64> {
+        self.flush();
+        self.totals
+    }
+}
+
+/// The sequence of four price changes that makes the most bananas, earliest in base 19 order if
+/// several tie, or `None` if there aren't any buyers.
+pub fn best_sequence(secrets: &[u64]) -> Option<BestSequence> {
+    if secrets.is_empty() {
+        return None;
+    }
+    let totals = secrets
+        .par_iter()
+        // Each split gets a tally of its own, so don't split finer than is worth the allocation.
+        .with_min_len(64)
+        .fold(Tally::new, |tally, &secret| tally.add_buyer(secret))
+        .map(Tally::into_totals)
+        .reduce(
+            || vec![0; WINDOWS],
+            |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                a
+            },
+        );
+    let (window, &bananas) = totals
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|&(_, bananas)| bananas)?;
+    Some(BestSequence {
+        changes: window_changes(window),
+        bananas,
+    })
+}
+
+#[aoc(day22, part2, Dense)]
+pub fn part2_dense(s: &str) -> Result<u64> {
+    let s = &*normalize_input(s);
+    let secrets = s
+        .lines()
+        .map(|line| {
+            line.parse::<u64>()
+                .with_context(|| format!("Bad secret {line:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(best_sequence(&secrets).context("No buyers")?.bananas)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_best_sequence() -> Result<()> {
+        let input = "1\n2\n3\n2024";
+        assert_eq!(
+            best_sequence(&[1, 2, 3, 2024]),
+            Some(BestSequence {
+                changes: [-2, 1, -1, 3],
+                bananas: 23,
+            })
+        );
+        assert_eq!(part2_dense(input)?, 23);
+        assert_eq!(part2_dense(&input.replace('\n', "\r\n"))?, 23);
+        assert_eq!(part2(input), 23);
+        assert_eq!(best_sequence(&[]), None);
+
+        let many = (1..=300_u64)
+            .map(|secret| (secret * 7919).to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(part2_dense(&many)?, part2_dash(&many));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tally_flushes() {
+        // Enough buyers in one tally that the narrow counts would overflow without flushing.
+        let best = best_sequence(&[1]).unwrap();
+        let buyers = 2 * BUYERS_PER_FLUSH as u64 + 5;
+        let totals = (0..buyers)
+            .fold(Tally::new(), |tally, _| tally.add_buyer(1))
+            .into_totals();
+        let (window, &bananas) = totals
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, bananas)| bananas)
+            .unwrap();
+        assert_eq!(window_changes(window), best.changes);
+        assert_eq!(bananas, buyers * best.bananas);
+    }
+
+    #[test]
+    fn test_window_changes() {
+        assert_eq!(window_changes(0), [-9, -9, -9, -9]);
+        assert_eq!(window_changes(WINDOWS - 1), [9, 9, 9, 9]);
+        assert_eq!(
+            window_changes(((9 * 19 + 10) * 19 + 8) * 19 + 12),
+            [0, 1, -1, 3]
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use super::*;
+use crate::clique::Graph;
+use anyhow::ensure;
 use rustc_hash::FxHashSet;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
@@ -10,12 +12,36 @@ const fn byte_pair_to_idx(first: u8, second: u8) -> usize {
     (first - b'a') as usize * 32 + (second - b'a') as usize
 }
 
+/// Checks that every line is a `ab-cd` pair of lowercase computer names, which the adjacency
+/// matrix indexing relies on.
+fn validate(input: &str) -> Result<()> {
+    ensure!(!input.is_empty(), "No connections given");
+    validate_lines(input, |_, line| {
+        check_line_shape(line, 5, |col, byte| {
+            if col == 2 {
+                byte == b'-'
+            } else {
+                byte.is_ascii_lowercase()
+            }
+        })
+    })
+}
+
 const TA_INDEX: usize = byte_pair_to_idx(b't', b'a');
 const TZ_INDEX: usize = byte_pair_to_idx(b't', b'z');
 const UPPER: usize = 25 * 32 + 25 + 1;
 
 #[aoc(day23, part1)]
-fn part1(input: &str) -> usize {
+fn part1(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    validate(input)?;
+    // SAFETY: validated above.
+    Ok(unsafe { part1_unchecked(input) })
+}
+
+/// # Safety
+/// `input` must have passed [`validate`].
+unsafe fn part1_unchecked(input: &str) -> usize {
     let adjacency_matrix: [[AtomicBool; UPPER]; UPPER] =
         unsafe { std::mem::transmute([[false; UPPER]; UPPER]) };
     input.par_lines().for_each(|line| {
@@ -56,7 +82,16 @@ fn part1(input: &str) -> usize {
 }
 
 #[aoc(day23, part2)]
-fn part2(input: &str) -> usize {
+fn part2(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    validate(input)?;
+    // SAFETY: validated above.
+    Ok(unsafe { part2_unchecked(input) })
+}
+
+/// # Safety
+/// `input` must have passed [`validate`].
+unsafe fn part2_unchecked(input: &str) -> usize {
     let adjacency_matrix: [[AtomicBool; UPPER]; UPPER] =
         unsafe { std::mem::transmute([[false; UPPER]; UPPER]) };
     input.par_lines().for_each(|line| {
@@ -133,3 +168,71 @@ fn bron_kerbosch(
         x.insert(vertex);
     }
 }
+
+/// Builds the network from `ab-cd` lines. Unlike [`validate`], names can be any length, as long
+/// as they're not empty and don't contain a `-`.
+fn parse_network(input: &str) -> Result<Graph<&str>> {
+    ensure!(!input.trim().is_empty(), "No connections given");
+    let edges = input
+        .lines()
+        .enumerate()
+        .map(|(line_no, line)| {
+            line.split_once('-')
+                .filter(|(a, b)| !a.is_empty() && !b.is_empty() && !b.contains('-'))
+                .with_context(|| format!("Line {}: expected two names joined by '-'", line_no + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Graph::from_edges(edges))
+}
+
+#[aoc(day23, part1, Clique)]
+fn part1_clique(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    let network = parse_network(input)?;
+    let mut total = 0;
+    network.for_each_clique_of_size(3, |triangle| {
+        total += network.labels(triangle).any(|name| name.starts_with('t')) as usize;
+    });
+    Ok(total)
+}
+
+/// The LAN party's password: the names in the largest group of connected computers, sorted and
+/// joined with commas.
+#[aoc(day23, part2, Clique)]
+fn part2_clique(input: &str) -> Result<String> {
+    let input = &*normalize_input(input);
+    let network = parse_network(input)?;
+    let mut names: Vec<_> = network.labels(&network.max_clique()).copied().collect();
+    names.sort_unstable();
+    Ok(names.join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "kh-tc\nqp-kh\nde-cg\nka-co\nyn-aq\nqp-ub\ncg-tb\nvc-aq\ntb-ka\nwh-tc\nyn-cg\nkh-ub\nta-co\nde-co\ntc-td\ntb-wq\nwh-td\nta-ka\ntd-qp\naq-cg\nwq-ub\nub-vc\nde-ta\nwq-aq\nwq-vc\nwh-yn\nka-de\nkh-ta\nco-tc\nwh-qp\ntb-vc\ntd-yn\n";
+
+    #[test]
+    fn test_clique() {
+        assert_eq!(part1_clique(EXAMPLE).unwrap(), 7);
+        assert_eq!(part2_clique(EXAMPLE).unwrap(), "co,de,ka,ta");
+        let long_names = EXAMPLE.replace("ta", "tango").replace("de", "delta");
+        assert_eq!(part1_clique(&long_names).unwrap(), 7);
+        assert_eq!(part2_clique(&long_names).unwrap(), "co,delta,ka,tango");
+        assert!(part1_clique("kh-tc\r\nqp-kh\r\n").is_ok());
+        assert!(part1_clique("kh-tc\n\nqp-kh").is_err());
+        assert!(part2_clique("kh-tc-qp").is_err());
+        assert!(part2_clique("").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(part1("kh-tc\nqp-kh\n").is_ok());
+        assert!(part1("kh-tc\r\nqp-kh\r\n").is_ok());
+        assert!(part1("kh-tc \nqp-kh\n").is_err());
+        assert!(part2("kh-Tc\nqp-kh").is_err());
+        assert!(part2("kh-tc\n\nqp-kh").is_err());
+        assert!(part2("").is_err());
+    }
+}
@@ -1,40 +1,62 @@
-use std::collections::{hash_map::Entry, VecDeque};
-
-use arrayvec::ArrayVec;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::{self, Write},
+};
 
 use super::*;
-use rustc_hash::FxHashMap;
+use anyhow::{anyhow, bail, ensure};
+use aoc_common::graph::Graph;
+use itertools::Itertools;
+use rustc_hash::{FxBuildHasher, FxHashMap, FxHashSet};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum Operation {
     And,
     Or,
     Xor,
 }
 
-#[derive(Clone, Debug)]
-struct Gate {
-    op: Operation,
-    inputs: ArrayVec<bool, 2>,
-    output: [u8; 3],
+type Wire = [u8; 3];
+
+fn wire_name(wire: &Wire) -> &str {
+    std::str::from_utf8(wire).unwrap_or("???")
 }
 
-impl Gate {
-    fn push_input(&mut self, input: bool) -> Option<([u8; 3], bool)> {
-        self.inputs.push(input);
-        (self.inputs.len() == 2).then(|| {
-            (
-                self.output,
-                match self.op {
-                    Operation::And => self.inputs[0] & self.inputs[1],
-                    Operation::Or => self.inputs[0] | self.inputs[1],
-                    Operation::Xor => self.inputs[0] ^ self.inputs[1],
-                },
-            )
+/// The wire carrying bit `bit` of the `x`, `y` or `z` bus.
+fn bus_wire(prefix: u8, bit: usize) -> Wire {
+    [prefix, b'0' + (bit / 10) as u8, b'0' + (bit % 10) as u8]
+}
+
+/// The bus prefix and bit number of a wire named like `x07`.
+fn bus_bit(wire: &Wire) -> Option<(u8, usize)> {
+    (wire[1].is_ascii_digit() && wire[2].is_ascii_digit()).then(|| {
+        (
+            wire[0],
+            (wire[1] - b'0') as usize * 10 + (wire[2] - b'0') as usize,
+        )
+    })
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Operation::And => "AND",
+            Operation::Or => "OR",
+            Operation::Xor => "XOR",
         })
     }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Gate {
+    op: Operation,
+    inputs: [Wire; 2],
+    output: Wire,
+}
 
-    fn from_line(line: &str) -> (Self, ([u8; 3], [u8; 3])) {
+impl Gate {
+    fn from_line(line: &str) -> Self {
         let bytes = line.as_bytes();
         let first_input = [bytes[0], bytes[1], bytes[2]];
         let (op, idx) = match bytes[4] {
@@ -45,172 +67,909 @@ impl Gate {
         };
         let second_input = [bytes[idx], bytes[idx + 1], bytes[idx + 2]];
         let output = [bytes[idx + 7], bytes[idx + 8], bytes[idx + 9]];
-        (
-            Self {
-                op,
-                output,
-                inputs: ArrayVec::new(),
-            },
-            (first_input, second_input),
+        Self {
+            op,
+            inputs: [first_input, second_input],
+            output,
+        }
+    }
+}
+
+impl Display for Gate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} -> {}",
+            wire_name(&self.inputs[0]),
+            self.op,
+            wire_name(&self.inputs[1]),
+            wire_name(&self.output)
         )
     }
 }
 
-#[derive(Clone, Debug)]
-enum Variable {
-    Value(bool),
-    Successors(Vec<usize>),
+/// A fixed-width, little-endian vector of bits, so buses can be wider than a `u64`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BitVector {
+    words: Vec<u64>,
+    len: usize,
 }
 
-impl Variable {
-    fn push_successor(&mut self, successor: usize) {
-        match self {
-            Variable::Successors(v) => v.push(successor),
-            Variable::Value(_) => unreachable!(),
+impl BitVector {
+    pub fn zeros(len: usize) -> Self {
+        BitVector {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    /// The low `len` bits of `value`.
+    pub fn from_u64(value: u64, len: usize) -> Self {
+        let mut bits = Self::zeros(len);
+        for bit in 0..len.min(64) {
+            bits.set(bit, value >> bit & 1 == 1);
+        }
+        bits
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, bit: usize) -> bool {
+        bit < self.len && self.words[bit / 64] >> (bit % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, bit: usize, value: bool) {
+        assert!(
+            bit < self.len,
+            "Bit {bit} out of range for {} bits",
+            self.len
+        );
+        let mask = 1 << (bit % 64);
+        if value {
+            self.words[bit / 64] |= mask;
+        } else {
+            self.words[bit / 64] &= !mask;
         }
     }
 
-    fn set_value(&mut self, value: bool) -> Vec<usize> {
-        match std::mem::replace(self, Variable::Value(value)) {
-            Variable::Successors(v) => v,
-            Variable::Value(_) => unreachable!(),
+    /// The value as a `u64`, if it fits.
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.words.split_first() {
+            None => Some(0),
+            Some((&low, high)) => high.iter().all(|&word| word == 0).then_some(low),
         }
     }
 
-    fn get_value(&self) -> Option<bool> {
-        match self {
-            Variable::Value(b) => Some(*b),
-            Variable::Successors(_) => None,
+    /// The sum, one bit wider than the wider of the two so it can't overflow.
+    pub fn add(&self, other: &Self) -> Self {
+        let mut sum = Self::zeros(self.len.max(other.len) + 1);
+        let mut carry = false;
+        for (idx, word) in sum.words.iter_mut().enumerate() {
+            let a = self.words.get(idx).copied().unwrap_or(0);
+            let b = other.words.get(idx).copied().unwrap_or(0);
+            let (partial, first_carry) = a.overflowing_add(b);
+            let (total, second_carry) = partial.overflowing_add(carry as u64);
+            *word = total;
+            carry = first_carry || second_carry;
         }
+        sum
     }
 }
 
-fn compute_circuit(
-    mut variables: FxHashMap<[u8; 3], Variable>,
-    mut gates: Vec<Gate>,
-    mut new_values: VecDeque<([u8; 3], bool)>,
-) -> u64 {
-    while let Some((tag, value)) = new_values.pop_front() {
-        let gates_to_resolve = match variables.entry(tag) {
-            Entry::Occupied(mut occupied) => occupied.get_mut().set_value(value),
-            Entry::Vacant(vacant) => {
-                vacant.insert(Variable::Value(value));
+impl Display for BitVector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for bit in (0..self.len).rev() {
+            f.write_str(if self.get(bit) { "1" } else { "0" })?;
+        }
+        Ok(())
+    }
+}
+
+/// The initial wire values and gates of a puzzle input.
+#[derive(Clone, Debug)]
+pub struct Netlist {
+    initial: Vec<(Wire, bool)>,
+    gates: Vec<Gate>,
+}
+
+/// A gate that breaks one of the local rules every gate of a ripple-carry adder follows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Deviation {
+    gate: Gate,
+    reason: &'static str,
+}
+
+impl Display for Deviation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.gate, self.reason)
+    }
+}
+
+/// The first bit at which the circuit stops matching a ripple-carry adder, along with the carry
+/// wire coming into that bit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Fault {
+    bit: usize,
+    carry_in: Option<Wire>,
+    reason: &'static str,
+}
+
+impl Netlist {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (initial, gates) = s
+            .split_once("\n\n")
+            .context("Missing blank line between the inputs and the gates")?;
+        let initial = initial
+            .lines()
+            .map(|line| {
+                let bytes = line.as_bytes();
+                ensure!(bytes.len() == 6, "Bad initial value {line:?}");
+                let value = match bytes[5] {
+                    b'0' => false,
+                    b'1' => true,
+                    _ => bail!("Bad initial value {line:?}"),
+                };
+                Ok(([bytes[0], bytes[1], bytes[2]], value))
+            })
+            .collect::<Result<_>>()?;
+        let gates = gates.lines().map(Gate::from_line).collect();
+        Ok(Netlist { initial, gates })
+    }
+
+    /// One more than the highest bit of the bus named `prefix` that's given a value or driven by
+    /// a gate.
+    fn bus_width(&self, prefix: u8) -> usize {
+        self.initial
+            .iter()
+            .map(|(wire, _)| wire)
+            .chain(self.gates.iter().map(|gate| &gate.output))
+            .filter_map(bus_bit)
+            .filter(|&(p, _)| p == prefix)
+            .map(|(_, bit)| bit + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Prefixes of the buses, named like `x07`, whose wires are all either undriven, for
+    /// `outputs == false`, or driven by a gate and never read, for `outputs == true`.
+    fn buses(&self, outputs: bool) -> Vec<(u8, usize)> {
+        let driven: FxHashSet<&Wire> = self.gates.iter().map(|gate| &gate.output).collect();
+        let read: FxHashSet<&Wire> = self.gates.iter().flat_map(|gate| &gate.inputs).collect();
+        let mut widths = BTreeMap::<u8, Option<usize>>::new();
+        for wire in self
+            .initial
+            .iter()
+            .map(|(wire, _)| wire)
+            .chain(driven.iter().copied())
+            .chain(read.iter().copied())
+        {
+            let Some((prefix, bit)) = bus_bit(wire) else {
                 continue;
+            };
+            let fits = if outputs {
+                driven.contains(wire) && !read.contains(wire)
+            } else {
+                !driven.contains(wire)
+            };
+            let width = widths.entry(prefix).or_insert(Some(0));
+            *width = width.filter(|_| fits).map(|width| width.max(bit + 1));
+        }
+        widths
+            .into_iter()
+            .filter_map(|(prefix, width)| Some((prefix, width?)))
+            .collect()
+    }
+
+    fn input_buses(&self) -> Vec<(u8, usize)> {
+        self.buses(false)
+    }
+
+    fn output_buses(&self) -> Vec<(u8, usize)> {
+        self.buses(true)
+    }
+
+    /// Orders the gates so each comes after the gates driving its inputs.
+    fn evaluation_order(&self) -> Result<Vec<usize>> {
+        // Each gate is the node for the wire it drives, so node ids are gate indices.
+        let mut circuit = Graph::<Wire, FxBuildHasher>::new();
+        for gate in &self.gates {
+            circuit.intern(gate.output);
+        }
+        ensure!(
+            circuit.len() == self.gates.len(),
+            "A wire is driven by more than one gate"
+        );
+        for gate in &self.gates {
+            for input in gate.inputs {
+                if circuit.id(&input).is_some() {
+                    circuit.add_edge(input, gate.output);
+                }
+            }
+        }
+        circuit.topological_order().ok_or_else(|| {
+            let cycle = circuit.find_cycle().unwrap_or_default();
+            anyhow!(
+                "The circuit has a loop through {}",
+                cycle
+                    .into_iter()
+                    .map(|id| wire_name(circuit.label(id)))
+                    .join(", ")
+            )
+        })
+    }
+
+    /// Evaluates 64 copies of the circuit at once, with bit `i` of every wire's value belonging to
+    /// copy `i`. Wires missing from `inputs` start with their initial value in every copy.
+    fn evaluate_lanes(&self, inputs: &FxHashMap<Wire, u64>) -> Result<FxHashMap<Wire, u64>> {
+        let mut values: FxHashMap<Wire, u64> = self
+            .initial
+            .iter()
+            .map(|&(wire, value)| (wire, if value { !0 } else { 0 }))
+            .collect();
+        values.extend(inputs);
+        for idx in self.evaluation_order()? {
+            let gate = &self.gates[idx];
+            let mut operands = [0; 2];
+            for (operand, input) in operands.iter_mut().zip(&gate.inputs) {
+                *operand = *values
+                    .get(input)
+                    .with_context(|| format!("Wire {} has no value", wire_name(input)))?;
+            }
+            let [a, b] = operands;
+            let value = match gate.op {
+                Operation::And => a & b,
+                Operation::Or => a | b,
+                Operation::Xor => a ^ b,
+            };
+            values.insert(gate.output, value);
+        }
+        Ok(values)
+    }
+
+    /// Evaluates the circuit on up to 64 sets of input bus values at once, returning the output
+    /// buses for each. Buses a set leaves out keep their initial values.
+    fn evaluate_batch(
+        &self,
+        batch: &[FxHashMap<u8, BitVector>],
+    ) -> Result<Vec<FxHashMap<u8, BitVector>>> {
+        ensure!(
+            batch.len() <= 64,
+            "Can only evaluate 64 sets of inputs at once"
+        );
+        let input_buses = self.input_buses();
+        let initial: FxHashMap<Wire, bool> = self.initial.iter().copied().collect();
+        let mut inputs = FxHashMap::<Wire, u64>::default();
+        for (lane, buses) in batch.iter().enumerate() {
+            for (&prefix, bits) in buses {
+                let &(_, width) = input_buses
+                    .iter()
+                    .find(|&&(p, _)| p == prefix)
+                    .with_context(|| format!("{} isn't an input bus", prefix as char))?;
+                ensure!(
+                    bits.len() <= width,
+                    "{} only has {width} bits",
+                    prefix as char
+                );
+                for bit in 0..width {
+                    let wire = bus_wire(prefix, bit);
+                    let lanes = inputs.entry(wire).or_insert(
+                        if initial.get(&wire).copied().unwrap_or(false) {
+                            !0
+                        } else {
+                            0
+                        },
+                    );
+                    *lanes = *lanes & !(1 << lane) | (bits.get(bit) as u64) << lane;
+                }
+            }
+        }
+
+        let values = self.evaluate_lanes(&inputs)?;
+        let output_buses = self.output_buses();
+        Ok((0..batch.len())
+            .map(|lane| {
+                output_buses
+                    .iter()
+                    .map(|&(prefix, width)| {
+                        let mut bits = BitVector::zeros(width);
+                        for bit in 0..width {
+                            let lanes = values.get(&bus_wire(prefix, bit)).copied().unwrap_or(0);
+                            bits.set(bit, lanes >> lane & 1 == 1);
+                        }
+                        (prefix, bits)
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn evaluate(&self, inputs: &FxHashMap<u8, BitVector>) -> Result<FxHashMap<u8, BitVector>> {
+        Ok(self
+            .evaluate_batch(std::slice::from_ref(inputs))?
+            .pop()
+            .unwrap())
+    }
+
+    /// The `z` the circuit computes from the given `x` and `y`.
+    pub fn evaluate_xy(&self, x: &BitVector, y: &BitVector) -> Result<BitVector> {
+        let inputs = [(b'x', x.clone()), (b'y', y.clone())].into_iter().collect();
+        self.evaluate(&inputs)?
+            .remove(&b'z')
+            .context("The circuit has no z bus")
+    }
+
+    /// Writes the circuit as a Graphviz digraph with a node per wire, coloured by the gate
+    /// driving it, and the `z` outputs lined up in bit order.
+    pub fn write_dot(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "digraph day24 {{")?;
+        writeln!(out, "  node [style=filled];")?;
+        for (wire, value) in &self.initial {
+            writeln!(
+                out,
+                "  {} [shape=invhouse, fillcolor=lightgrey, label=\"{}\\n{}\"];",
+                wire_name(wire),
+                wire_name(wire),
+                *value as u8
+            )?;
+        }
+        for gate in &self.gates {
+            let colour = match gate.op {
+                Operation::And => "lightblue",
+                Operation::Or => "palegreen",
+                Operation::Xor => "lightsalmon",
+            };
+            let shape = if gate.output[0] == b'z' {
+                "doublecircle"
+            } else {
+                "ellipse"
+            };
+            writeln!(
+                out,
+                "  {} [shape={shape}, fillcolor={colour}, label=\"{}\\n{}\"];",
+                wire_name(&gate.output),
+                wire_name(&gate.output),
+                gate.op
+            )?;
+            for input in &gate.inputs {
+                writeln!(
+                    out,
+                    "  {} -> {};",
+                    wire_name(input),
+                    wire_name(&gate.output)
+                )?;
+            }
+        }
+        let outputs = (0..self.bus_width(b'z'))
+            .map(|bit| wire_name(&bus_wire(b'z', bit)).to_owned())
+            .join(" -> ");
+        writeln!(out, "  {{ rank=same; {outputs} [style=invis]; }}")?;
+        writeln!(out, "}}")
+    }
+
+    /// Writes the circuit as a structural Verilog module with `x`, `y` and `z` as buses and a
+    /// primitive gate instance per gate.
+    pub fn write_verilog(&self, mut out: impl Write) -> io::Result<()> {
+        fn net(wire: &Wire) -> String {
+            match bus_bit(wire) {
+                Some((prefix @ (b'x' | b'y' | b'z'), bit)) => format!("{}[{bit}]", prefix as char),
+                // Prefixed so wires like `and` or `not` can't collide with keywords.
+                _ => format!("w_{}", wire_name(wire)),
             }
+        }
+
+        let (x_width, y_width, z_width) = (
+            self.bus_width(b'x'),
+            self.bus_width(b'y'),
+            self.bus_width(b'z'),
+        );
+        writeln!(out, "module day24(")?;
+        writeln!(out, "    input [{}:0] x,", x_width.max(1) - 1)?;
+        writeln!(out, "    input [{}:0] y,", y_width.max(1) - 1)?;
+        writeln!(out, "    output [{}:0] z", z_width.max(1) - 1)?;
+        writeln!(out, ");")?;
+        for gate in &self.gates {
+            if !net(&gate.output).contains('[') {
+                writeln!(out, "    wire {};", net(&gate.output))?;
+            }
+        }
+        for (idx, gate) in self.gates.iter().enumerate() {
+            let primitive = match gate.op {
+                Operation::And => "and",
+                Operation::Or => "or",
+                Operation::Xor => "xor",
+            };
+            writeln!(
+                out,
+                "    {primitive} g{idx}({}, {}, {});",
+                net(&gate.output),
+                net(&gate.inputs[0]),
+                net(&gate.inputs[1])
+            )?;
+        }
+        writeln!(out, "endmodule")
+    }
+
+    /// Writes the initial values and gates as JSON.
+    pub fn write_json(&self, mut out: impl Write) -> io::Result<()> {
+        fn string(wire: &Wire) -> String {
+            let mut s = String::from('"');
+            for c in wire_name(wire).chars() {
+                match c {
+                    '"' | '\\' => s.extend(['\\', c]),
+                    c if c.is_control() => s += &format!("\\u{:04x}", c as u32),
+                    c => s.push(c),
+                }
+            }
+            s.push('"');
+            s
+        }
+
+        writeln!(out, "{{")?;
+        writeln!(out, "  \"inputs\": {{")?;
+        for (idx, (wire, value)) in self.initial.iter().enumerate() {
+            let comma = if idx + 1 < self.initial.len() {
+                ","
+            } else {
+                ""
+            };
+            writeln!(out, "    {}: {}{comma}", string(wire), *value as u8)?;
+        }
+        writeln!(out, "  }},")?;
+        writeln!(out, "  \"gates\": [")?;
+        for (idx, gate) in self.gates.iter().enumerate() {
+            let comma = if idx + 1 < self.gates.len() { "," } else { "" };
+            writeln!(
+                out,
+                "    {{\"op\": \"{}\", \"inputs\": [{}, {}], \"output\": {}}}{comma}",
+                gate.op,
+                string(&gate.inputs[0]),
+                string(&gate.inputs[1]),
+                string(&gate.output)
+            )?;
+        }
+        writeln!(out, "  ]")?;
+        writeln!(out, "}}")
+    }
+
+    fn swap_outputs(&mut self, a: Wire, b: Wire) {
+        for gate in &mut self.gates {
+            if gate.output == a {
+                gate.output = b;
+            } else if gate.output == b {
+                gate.output = a;
+            }
+        }
+    }
+
+    /// Checks every gate against the rules each gate of a ripple-carry adder follows, so the
+    /// result lists every misplaced output, not just the first.
+    fn deviations(&self) -> Vec<Deviation> {
+        let width = self.bus_width(b'x');
+        let final_carry = bus_wire(b'z', width);
+        let mut consumers = FxHashMap::<Wire, Vec<Operation>>::default();
+        for gate in &self.gates {
+            for input in gate.inputs {
+                consumers.entry(input).or_default().push(gate.op);
+            }
+        }
+        let feeds =
+            |wire: &Wire, op: Operation| consumers.get(wire).is_some_and(|ops| ops.contains(&op));
+        let is_input = |wire: &Wire| matches!(wire[0], b'x' | b'y');
+        let is_first_bit = |gate: &Gate| {
+            gate.inputs
+                .iter()
+                .any(|wire| is_input(wire) && bus_bit(wire).is_some_and(|(_, bit)| bit == 0))
         };
-        new_values.extend(
-            gates_to_resolve
-                .into_iter()
-                .filter_map(|idx| gates[idx].push_input(value)),
+
+        let mut deviations = Vec::new();
+        for gate in &self.gates {
+            let reads_inputs = gate.inputs.iter().all(is_input);
+            let reason = match gate.op {
+                _ if gate.output == final_carry => (gate.op != Operation::Or && width > 1)
+                    .then_some("the final carry should come from an OR"),
+                _ if gate.output[0] == b'z' && gate.op != Operation::Xor => {
+                    Some("sum bits should come from an XOR")
+                }
+                Operation::Xor if !reads_inputs && gate.output[0] != b'z' => {
+                    Some("an XOR with the carry should output a sum bit")
+                }
+                Operation::Xor
+                    if reads_inputs
+                        && !is_first_bit(gate)
+                        && !feeds(&gate.output, Operation::Xor) =>
+                {
+                    Some("x ^ y should feed the XOR with the carry")
+                }
+                Operation::And if !is_first_bit(gate) && !feeds(&gate.output, Operation::Or) => {
+                    Some("an AND should feed the carry's OR")
+                }
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                deviations.push(Deviation {
+                    gate: gate.clone(),
+                    reason,
+                });
+            }
+        }
+        deviations
+    }
+
+    /// Walks the circuit a bit at a time, checking it computes `z = x + y` with exactly the
+    /// half-adder, full-adder, full-adder, ... structure of a ripple-carry adder.
+    fn verify(&self) -> Result<(), Fault> {
+        let by_inputs: FxHashMap<(Operation, Wire, Wire), Wire> = self
+            .gates
+            .iter()
+            .map(|gate| {
+                let [a, b] = gate.inputs;
+                ((gate.op, a.min(b), a.max(b)), gate.output)
+            })
+            .collect();
+        let find =
+            |op: Operation, a: Wire, b: Wire| by_inputs.get(&(op, a.min(b), a.max(b))).copied();
+
+        let width = self.bus_width(b'x');
+        let mut carry = None;
+        for bit in 0..width {
+            let fault = |reason| Fault {
+                bit,
+                carry_in: carry,
+                reason,
+            };
+            let (x, y, z) = (
+                bus_wire(b'x', bit),
+                bus_wire(b'y', bit),
+                bus_wire(b'z', bit),
+            );
+            let half_sum = find(Operation::Xor, x, y).ok_or(fault("no XOR of the inputs"))?;
+            let half_carry = find(Operation::And, x, y).ok_or(fault("no AND of the inputs"))?;
+            let Some(carry_in) = carry else {
+                if half_sum != z {
+                    return Err(fault("the first sum bit isn't x ^ y"));
+                }
+                carry = Some(half_carry);
+                continue;
+            };
+            let sum = find(Operation::Xor, half_sum, carry_in)
+                .ok_or(fault("no XOR of x ^ y with the carry"))?;
+            if sum != z {
+                return Err(fault("the sum bit isn't x ^ y ^ carry"));
+            }
+            let carried = find(Operation::And, half_sum, carry_in)
+                .ok_or(fault("no AND of x ^ y with the carry"))?;
+            carry = Some(
+                find(Operation::Or, half_carry, carried)
+                    .ok_or(fault("no OR of the partial carries"))?,
+            );
+        }
+        if carry.is_some_and(|carry| carry != bus_wire(b'z', width)) {
+            return Err(Fault {
+                bit: width,
+                carry_in: carry,
+                reason: "the top bit isn't the final carry",
+            });
+        }
+        Ok(())
+    }
+
+    /// Outputs of the gates within two steps of the inputs to the faulty bit, or producing its
+    /// sum bit, one of which must be swapped to get past the fault.
+    fn suspects(&self, fault: &Fault) -> Vec<Wire> {
+        let mut seeds: FxHashSet<Wire> = [bus_wire(b'x', fault.bit), bus_wire(b'y', fault.bit)]
+            .into_iter()
+            .chain(fault.carry_in)
+            .collect();
+        let z = bus_wire(b'z', fault.bit);
+        let mut suspects: Vec<Wire> = Vec::new();
+        for _ in 0..2 {
+            let outputs: Vec<Wire> = self
+                .gates
+                .iter()
+                .filter(|gate| gate.inputs.iter().any(|input| seeds.contains(input)))
+                .map(|gate| gate.output)
+                .collect();
+            seeds.extend(outputs.iter().copied());
+            suspects.extend(outputs);
+        }
+        suspects.extend(
+            self.gates
+                .iter()
+                .map(|gate| gate.output)
+                .filter(|&wire| wire == z),
         );
+        suspects.sort_unstable();
+        suspects.dedup();
+        suspects
     }
 
-    let mut z = 0_u64;
-    for i in (b'0'..=b'4').rev() {
-        z = (z << 1)
-            | variables
-                .get(&[b'z', b'6', i])
-                .and_then(|var| var.get_value())
-                .unwrap_or(false) as u64;
+    /// Finds the fewest output swaps, up to `max_swaps`, that turn the circuit into a
+    /// ripple-carry adder.
+    fn repair(&self, max_swaps: usize) -> Option<Vec<(Wire, Wire)>> {
+        (0..=max_swaps).find_map(|depth| self.repair_within(depth))
     }
-    for i in (b'0'..=b'5').rev() {
-        for j in (b'0'..=b'9').rev() {
-            z = (z << 1)
-                | variables
-                    .get(&[b'z', i, j])
-                    .and_then(|var| var.get_value())
-                    .unwrap_or(false) as u64;
+
+    fn repair_within(&self, depth: usize) -> Option<Vec<(Wire, Wire)>> {
+        let Err(fault) = self.verify() else {
+            return Some(Vec::new());
+        };
+        if depth == 0 {
+            return None;
+        }
+        for a in self.suspects(&fault) {
+            for b in self.gates.iter().map(|gate| gate.output) {
+                if a == b {
+                    continue;
+                }
+                let mut swapped = self.clone();
+                swapped.swap_outputs(a, b);
+                // Every swap has to get the walk further along, or it isn't fixing this bit.
+                if swapped.verify().is_err_and(|next| next.bit <= fault.bit) {
+                    continue;
+                }
+                if let Some(mut swaps) = swapped.repair_within(depth - 1) {
+                    swaps.push((a, b));
+                    return Some(swaps);
+                }
+            }
         }
+        None
     }
-    z
 }
 
 #[aoc(day24, part1)]
-pub fn part1(s: &str) -> u64 {
-    let mut variables = FxHashMap::<[u8; 3], Variable>::default();
-    let (initial_variables, gates) = s.split_once("\n\n").unwrap();
-    let gates = gates
-        .lines()
-        .map(Gate::from_line)
-        .enumerate()
-        .map(|(idx, triple)| {
-            variables
-                .entry(triple.1 .0)
-                .and_modify(|var| var.push_successor(idx))
-                .or_insert(Variable::Successors(vec![idx; 1]));
-            variables
-                .entry(triple.1 .1)
-                .and_modify(|var| var.push_successor(idx))
-                .or_insert(Variable::Successors(vec![idx; 1]));
-            triple.0
-        })
-        .collect::<Vec<_>>();
-    let new_values = initial_variables
-        .lines()
-        .map(|line| {
-            let bytes = line.as_bytes();
-            let tag = [bytes[0], bytes[1], bytes[2]];
-            let value = match bytes[5] {
-                b'0' => false,
-                b'1' => true,
-                _ => unreachable!(),
-            };
-            (tag, value)
-        })
-        .collect::<VecDeque<_>>();
-
-    compute_circuit(variables, gates, new_values)
+pub fn part1(s: &str) -> Result<u64> {
+    let s = &*normalize_input(s);
+    Netlist::parse(s)?
+        .evaluate(&FxHashMap::default())?
+        .remove(&b'z')
+        .context("The circuit has no z bus")?
+        .to_u64()
+        .context("z doesn't fit in 64 bits")
 }
 
 #[aoc(day24, part2)]
-pub fn part2(s: &str) -> String {
-    let mut variables = FxHashMap::<[u8; 3], Variable>::default();
-    let (initial_variables, gates) = s.split_once("\n\n").unwrap();
-    let gates = gates
-        .lines()
-        .map(Gate::from_line)
-        .enumerate()
-        .map(|(idx, triple)| {
-            variables
-                .entry(triple.1 .0)
-                .and_modify(|var| var.push_successor(idx))
-                .or_insert(Variable::Successors(vec![idx; 1]));
-            variables
-                .entry(triple.1 .1)
-                .and_modify(|var| var.push_successor(idx))
-                .or_insert(Variable::Successors(vec![idx; 1]));
-            triple.0
-        })
-        .collect::<Vec<_>>();
-    let new_values = initial_variables
-        .lines()
-        .map(|line| {
-            let bytes = line.as_bytes();
-            let tag = [bytes[0], bytes[1], bytes[2]];
-            let value = match bytes[5] {
-                b'0' => false,
-                b'1' => true,
-                _ => unreachable!(),
-            };
-            (tag, value)
-        })
-        .collect::<VecDeque<_>>();
+pub fn part2(s: &str) -> Result<String> {
+    let s = &*normalize_input(s);
+    let netlist = Netlist::parse(s)?;
+    let swaps = netlist.repair(4).with_context(|| {
+        format!(
+            "No four swaps make the circuit an adder. Suspicious gates:\n{}",
+            netlist.deviations().iter().join("\n")
+        )
+    })?;
+    Ok(swaps
+        .iter()
+        .flat_map(|(a, b)| [a, b])
+        .map(wire_name)
+        .sorted_unstable()
+        .join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufWriter;
+    use std::path::Path;
+
+    static INPUT: &str = include_str!("../input/2024/day24.txt");
+
+    /// A ripple-carry adder over `bits` bits, with the named outputs swapped.
+    fn adder(bits: usize, swaps: &[(&str, &str)]) -> String {
+        let mut s = String::new();
+        for prefix in ["x", "y"] {
+            for bit in 0..bits {
+                s += &format!("{prefix}{bit:02}: {}\n", bit % 2);
+            }
+        }
+        s.push('\n');
+        let mut gate = |a: String, op: &str, b: String, out: String| {
+            let out = swaps
+                .iter()
+                .find_map(|&(x, y)| (out == x).then_some(y).or((out == y).then_some(x)))
+                .map_or(out, str::to_owned);
+            s += &format!("{a} {op} {b} -> {out}\n");
+        };
+        gate("x00".into(), "XOR", "y00".into(), "z00".into());
+        gate("y00".into(), "AND", "x00".into(), "c00".into());
+        for bit in 1..bits {
+            let carry = format!("c{:02}", bit - 1);
+            gate(
+                format!("x{bit:02}"),
+                "XOR",
+                format!("y{bit:02}"),
+                format!("s{bit:02}"),
+            );
+            gate(
+                format!("x{bit:02}"),
+                "AND",
+                format!("y{bit:02}"),
+                format!("a{bit:02}"),
+            );
+            gate(
+                carry.clone(),
+                "XOR",
+                format!("s{bit:02}"),
+                format!("z{bit:02}"),
+            );
+            gate(format!("s{bit:02}"), "AND", carry, format!("t{bit:02}"));
+            gate(
+                format!("a{bit:02}"),
+                "OR",
+                format!("t{bit:02}"),
+                format!("c{bit:02}"),
+            );
+        }
+        s.replace(&format!("c{:02}\n", bits - 1), &format!("z{bits:02}\n"))
+    }
+
+    #[test]
+    fn test_correct_adder() -> Result<()> {
+        let netlist = Netlist::parse(&adder(8, &[]))?;
+        assert_eq!(netlist.verify(), Ok(()));
+        assert_eq!(netlist.deviations(), []);
+        assert_eq!(part1(&adder(8, &[]))?, 0b10101010 * 2);
+        assert_eq!(part2(&adder(8, &[]))?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair() -> Result<()> {
+        let s = adder(
+            12,
+            &[
+                ("z02", "c02"),
+                ("s05", "a05"),
+                ("z07", "t07"),
+                ("t09", "c10"),
+            ],
+        );
+        let netlist = Netlist::parse(&s)?;
+        assert_eq!(netlist.verify().unwrap_err().bit, 2);
+        let deviations = netlist.deviations();
+        let deviating: Vec<&str> = deviations
+            .iter()
+            .map(|deviation| wire_name(&deviation.gate.output))
+            .sorted_unstable()
+            .collect();
+        assert_eq!(deviating, ["a05", "c02", "c10", "s05", "t07", "z02", "z07"]);
+        assert_eq!(part2(&s)?, "a05,c02,c10,s05,t07,t09,z02,z07");
+        Ok(())
+    }
 
-    let y_start = new_values.len() / 2;
-    let mut x = 0_u64;
-    let mut y = 0_u64;
-    for i in (0..y_start).rev() {
-        x = (x << 1) | new_values[i].1 as u64;
-        y = (y << 1) | new_values[i + y_start].1 as u64;
+    #[test]
+    fn test_buses() -> Result<()> {
+        let netlist = Netlist::parse(&adder(4, &[]))?;
+        assert_eq!(netlist.input_buses(), [(b'x', 4), (b'y', 4)]);
+        assert_eq!(netlist.output_buses(), [(b'z', 5)]);
+        Ok(())
     }
-    let target_z = x + y;
-    let actual_z = compute_circuit(variables.clone(), gates.clone(), new_values.clone());
-    let mismatches = target_z ^ actual_z;
-    for i in 0..64 {
-        if (mismatches >> i) % 2 != 0 {
-            println!("mismatch at bit {i}");
+
+    #[test]
+    fn test_bit_vector() {
+        let a = BitVector::from_u64(u64::MAX, 70);
+        let b = BitVector::from_u64(3, 70);
+        let sum = a.add(&b);
+        assert_eq!(sum.len(), 71);
+        assert_eq!(sum.to_u64(), None);
+        assert_eq!(
+            sum.to_string(),
+            format!("{}1{}10", "0".repeat(6), "0".repeat(62))
+        );
+        assert_eq!(BitVector::from_u64(0b1011, 4).to_u64(), Some(0b1011));
+        assert!(BitVector::zeros(0).is_empty());
+        assert_eq!(BitVector::zeros(0).add(&BitVector::zeros(0)).len(), 1);
+    }
+
+    #[test]
+    fn test_evaluate_wide_adder() -> Result<()> {
+        let width = 70;
+        let netlist = Netlist::parse(&adder(width, &[]))?;
+        let mut next = xorshift(0x9e37_79b9_7f4a_7c15_u64);
+        let mut random = || {
+            let mut bits = BitVector::zeros(width);
+            for bit in 0..width {
+                bits.set(bit, next(2) == 1);
+            }
+            bits
+        };
+        let batch: Vec<FxHashMap<u8, BitVector>> = (0..64)
+            .map(|_| [(b'x', random()), (b'y', random())].into_iter().collect())
+            .collect();
+        let outputs = netlist.evaluate_batch(&batch)?;
+        for (inputs, outputs) in batch.iter().zip(&outputs) {
+            assert_eq!(outputs[&b'z'], inputs[&b'x'].add(&inputs[&b'y']));
         }
+
+        let x = BitVector::from_u64(u64::MAX, width);
+        let y = BitVector::from_u64(1, width);
+        assert_eq!(netlist.evaluate_xy(&x, &y)?, x.add(&y));
+
+        // The swapped adder gets 1 + 1 wrong, and a loop can't be evaluated at all.
+        let swapped = Netlist::parse(&adder(4, &[("z01", "a01")]))?;
+        let one = BitVector::from_u64(1, 4);
+        assert_ne!(swapped.evaluate_xy(&one, &one)?, one.add(&one));
+        let looped = Netlist::parse(&adder(4, &[("c01", "z02")]))?;
+        let error = looped.evaluate_xy(&one, &one).unwrap_err().to_string();
+        assert!(
+            error.starts_with("The circuit has a loop through"),
+            "{error}"
+        );
+        Ok(())
     }
 
-    "".to_owned()
+    #[test]
+    fn test_exports() -> Result<()> {
+        let netlist = Netlist::parse(&adder(2, &[("z01", "a01")]))?;
+
+        let mut verilog = Vec::new();
+        netlist.write_verilog(&mut verilog)?;
+        assert_eq!(
+            String::from_utf8(verilog)?,
+            "module day24(
+    input [1:0] x,
+    input [1:0] y,
+    output [2:0] z
+);
+    wire w_c00;
+    wire w_s01;
+    wire w_a01;
+    wire w_t01;
+    xor g0(z[0], x[0], y[0]);
+    and g1(w_c00, y[0], x[0]);
+    xor g2(w_s01, x[1], y[1]);
+    and g3(z[1], x[1], y[1]);
+    xor g4(w_a01, w_c00, w_s01);
+    and g5(w_t01, w_s01, w_c00);
+    or g6(z[2], w_a01, w_t01);
+endmodule
+"
+        );
+
+        let mut dot = Vec::new();
+        netlist.write_dot(&mut dot)?;
+        let dot = String::from_utf8(dot)?;
+        assert!(
+            dot.contains("  a01 [shape=ellipse, fillcolor=lightsalmon, label=\"a01\\nXOR\"];\n")
+        );
+        assert!(
+            dot.contains("  z01 [shape=doublecircle, fillcolor=lightblue, label=\"z01\\nAND\"];\n")
+        );
+        assert!(dot.contains("  x01 -> s01;\n"));
+        assert!(dot.contains("{ rank=same; z00 -> z01 -> z02 [style=invis]; }"));
+
+        let mut json = Vec::new();
+        netlist.write_json(&mut json)?;
+        let json = String::from_utf8(json)?;
+        assert!(json.starts_with("{\n  \"inputs\": {\n    \"x00\": 0,\n    \"x01\": 1,\n"));
+        assert!(json.contains(
+            "    {\"op\": \"OR\", \"inputs\": [\"a01\", \"t01\"], \"output\": \"z02\"}\n  ]\n}\n"
+        ));
+        Ok(())
+    }
+
+    /// Regenerates `output/day24.{dot,v,json}` from the real input; run it with `--ignored`.
+    #[test]
+    #[ignore]
+    fn generate_netlist_exports() -> Result<()> {
+        let netlist = Netlist::parse(INPUT)?;
+        let output = Path::new(file!())
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("output");
+        std::fs::create_dir_all(&output)?;
+        netlist.write_dot(BufWriter::new(File::create(output.join("day24.dot"))?))?;
+        netlist.write_verilog(BufWriter::new(File::create(output.join("day24.v"))?))?;
+        netlist.write_json(BufWriter::new(File::create(output.join("day24.json"))?))?;
+        Ok(())
+    }
 }
@@ -1,10 +1,62 @@
 use super::*;
+use anyhow::{bail, ensure};
 use itertools::{Either, Itertools};
 use std::arch::x86_64::*;
 use std::sync::Mutex;
 
+/// Counts of each schematic kind, as found by [`validate`].
+struct Schematics {
+    keys: usize,
+    locks: usize,
+}
+
+/// Checks that `input` is a blank-line separated list of 7x5 `#`/`.` schematics whose top and
+/// bottom rows are solid, which is the layout the SIMD paths read from fixed offsets.
+fn validate(input: &str) -> Result<Schematics> {
+    let mut schematics = Schematics { keys: 0, locks: 0 };
+    let mut top = b'#';
+    let mut rows = 0;
+    validate_lines(input, |line_no, line| {
+        rows = line_no % 8 + 1;
+        match line_no % 8 {
+            7 => ensure!(line.is_empty(), "Expected a blank line between schematics"),
+            row => {
+                check_line_shape(line, 5, |_, byte| byte == b'#' || byte == b'.')?;
+                let solid = |c| line.iter().all(|&byte| byte == c);
+                if row == 0 {
+                    top = line[0];
+                    ensure!(solid(top), "Top row must be solid");
+                    if top == b'#' {
+                        schematics.locks += 1;
+                    } else {
+                        schematics.keys += 1;
+                    }
+                } else if row == 6 {
+                    let bottom = if top == b'#' { b'.' } else { b'#' };
+                    ensure!(solid(bottom), "Bottom row must be solid");
+                }
+            }
+        }
+        Ok(())
+    })?;
+    if rows != 7 {
+        bail!("Last schematic is incomplete");
+    }
+    Ok(schematics)
+}
+
+/// Validates `input` before handing it to one of the SIMD paths, which require AVX2 and index
+/// schematics without bounds checks.
+fn run_simd(input: &str, fast_path: unsafe fn(&str) -> usize) -> Result<usize> {
+    ensure!(is_x86_feature_detected!("avx2"), "AVX2 is not available");
+    validate(input)?;
+    // SAFETY: validated above.
+    Ok(unsafe { fast_path(input) })
+}
+
 #[aoc(day25, part1)]
 pub fn part1(input: &str) -> usize {
+    let input = &*normalize_input(input);
     let (keys, locks) = input
         .split("\n\n")
         .partition_map::<Vec<_>, Vec<_>, _, _, _>(|block| {
@@ -37,7 +89,14 @@ pub fn part1(input: &str) -> usize {
 }
 
 #[aoc(day25, part1, SimdCmp)]
-pub fn part1_simd_cmp(input: &str) -> usize {
+pub fn part1_simd_cmp(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    run_simd(input, part1_simd_cmp_unchecked)
+}
+
+/// # Safety
+/// `input` must have passed [`validate`], and AVX2 must be available.
+unsafe fn part1_simd_cmp_unchecked(input: &str) -> usize {
     let input = input.as_bytes();
     let (keys, locks) = input
         .chunks(43)
@@ -70,7 +129,14 @@ pub fn part1_simd_cmp(input: &str) -> usize {
 }
 
 #[aoc(day25, part1, SimdBetterCmp)]
-pub fn part1_simd_better_cmp(input: &str) -> usize {
+pub fn part1_simd_better_cmp(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    run_simd(input, part1_simd_better_cmp_unchecked)
+}
+
+/// # Safety
+/// `input` must have passed [`validate`], and AVX2 must be available.
+unsafe fn part1_simd_better_cmp_unchecked(input: &str) -> usize {
     let input = input.as_bytes();
     let (keys, locks) = input
         .chunks(43)
@@ -102,7 +168,14 @@ pub fn part1_simd_better_cmp(input: &str) -> usize {
 }
 
 #[aoc(day25, part1, SimdBuckwild)]
-pub fn part1_simd_buckwild(input: &str) -> usize {
+pub fn part1_simd_buckwild(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    run_simd(input, part1_simd_buckwild_unchecked)
+}
+
+/// # Safety
+/// `input` must have passed [`validate`], and AVX2 must be available.
+unsafe fn part1_simd_buckwild_unchecked(input: &str) -> usize {
     unsafe {
         let all_hash = _mm256_broadcastsi128_si256(_mm_set1_epi8(b'#' as i8));
         let input = input.as_bytes();
@@ -134,10 +207,17 @@ pub fn part1_simd_buckwild(input: &str) -> usize {
 }
 
 #[aoc(day25, part1, SimdBuckwildPrealloc)]
-pub fn part1_simd_buckwild_prealloc(input: &str) -> usize {
+pub fn part1_simd_buckwild_prealloc(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    run_simd(input, part1_simd_buckwild_prealloc_unchecked)
+}
+
+/// # Safety
+/// `input` must have passed [`validate`], and AVX2 must be available.
+unsafe fn part1_simd_buckwild_prealloc_unchecked(input: &str) -> usize {
     let input = input.as_bytes();
-    let mut keys = Vec::with_capacity(input.len() / 64);
-    let mut locks = Vec::with_capacity(input.len() / 64);
+    let mut keys = Vec::with_capacity(input.len() / 43 + 1);
+    let mut locks = Vec::with_capacity(input.len() / 43 + 1);
     unsafe {
         let all_hash = _mm256_broadcastsi128_si256(_mm_set1_epi8(b'#' as i8));
         input
@@ -169,10 +249,17 @@ pub fn part1_simd_buckwild_prealloc(input: &str) -> usize {
 }
 
 #[aoc(day25, part1, SimdBuckwildPreallocFilter)]
-pub fn part1_simd_buckwild_prealloc_filter(input: &str) -> usize {
+pub fn part1_simd_buckwild_prealloc_filter(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    run_simd(input, part1_simd_buckwild_prealloc_filter_unchecked)
+}
+
+/// # Safety
+/// `input` must have passed [`validate`], and AVX2 must be available.
+unsafe fn part1_simd_buckwild_prealloc_filter_unchecked(input: &str) -> usize {
     let input = input.as_bytes();
-    let mut keys = Vec::with_capacity(input.len() / 64);
-    let mut locks = Vec::with_capacity(input.len() / 64);
+    let mut keys = Vec::with_capacity(input.len() / 43 + 1);
+    let mut locks = Vec::with_capacity(input.len() / 43 + 1);
     unsafe {
         let all_hash = _mm256_broadcastsi128_si256(_mm_set1_epi8(b'#' as i8));
         input
@@ -217,7 +304,20 @@ static DATA: Mutex<Data> = Mutex::new(Data {
 });
 
 #[aoc(day25, part1, SimdBigAssumptions)]
-pub fn part1_simd_big_assumptions(input: &str) -> usize {
+pub fn part1_simd_big_assumptions(input: &str) -> Result<usize> {
+    let input = &*normalize_input(input);
+    ensure!(is_x86_feature_detected!("avx2"), "AVX2 is not available");
+    let Schematics { keys, locks } = validate(input)?;
+    ensure!(keys == 250, "Expected exactly 250 keys, found {keys}");
+    ensure!(locks <= 256, "Expected at most 256 locks, found {locks}");
+    // SAFETY: validated above.
+    Ok(unsafe { part1_simd_big_assumptions_unchecked(input) })
+}
+
+/// # Safety
+/// `input` must have passed [`validate`] with exactly 250 keys and at most 256 locks, and AVX2
+/// must be available.
+unsafe fn part1_simd_big_assumptions_unchecked(input: &str) -> usize {
     let input = input.as_bytes();
     let mut lock = DATA.lock().unwrap();
     let data: &mut Data = &mut *lock;
@@ -277,3 +377,39 @@ pub fn part1_simd_big_assumptions(input: &str) -> usize {
         _mm_cvtsi128_si32(count) as u32 as usize
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "#####
+.####
+.####
+.####
+.#.#.
+.#...
+.....
+
+.....
+#....
+#....
+#...#
+#.#.#
+#.###
+#####
+";
+
+    #[test]
+    fn validates_schematics() {
+        assert!(validate(EXAMPLE).is_ok());
+        assert!(validate(&EXAMPLE.replace('\n', "\r\n")).is_err());
+        assert!(validate(&normalize_input(&EXAMPLE.replace('\n', "\r\n"))).is_ok());
+        assert!(validate(EXAMPLE.trim_end()).is_ok());
+        assert!(validate(&EXAMPLE.replacen(".####", ".### ", 1)).is_err());
+        assert!(validate(&EXAMPLE[..EXAMPLE.len() - 7]).is_err());
+        assert_eq!(part1(EXAMPLE), 0);
+        if is_x86_feature_detected!("avx2") {
+            assert_eq!(part1_simd_buckwild(EXAMPLE).unwrap(), 0);
+        }
+    }
+}
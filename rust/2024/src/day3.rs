@@ -25,6 +25,7 @@ impl HeuristicFrequencyRank for Emprical {
 
 #[aoc(day3, part1, Naive)]
 pub fn part1_naive(input: &str) -> u32 {
+    let input = &*normalize_input(input);
     let regex = Regex::new(r"mul\((?<a>[0-9]{1,3}),(?<b>[0-9]{1,3})\)").unwrap();
     regex
         .captures_iter(input)
@@ -45,6 +46,7 @@ fn parse_3(s: &str) -> u32 {
 
 #[aoc(day3, part1, Opt)]
 pub fn part1_opt(s: &str) -> u32 {
+    let s = &*normalize_input(s);
     let input = s.as_bytes();
     let mut i = 0;
     let mut sum = 0;
@@ -64,6 +66,7 @@ pub fn part1_opt(s: &str) -> u32 {
 
 #[aoc(day3, part1, Memchr)]
 pub fn part1(s: &str) -> u32 {
+    let s = &*normalize_input(s);
     let input = s.as_bytes();
     if input.len() < 4 {
         return 0;
@@ -149,6 +152,7 @@ unsafe fn match_mul_suffix(m_index: usize, input: &[u8]) -> (Option<u32>, usize)
 
 #[aoc(day3, part2, Naive)]
 pub fn part2_naive(input: &str) -> u32 {
+    let input = &*normalize_input(input);
     let regex = Regex::new(r"do\(\)|don't\(\)|mul\((?<a>[0-9]{1,3}),(?<b>[0-9]{1,3})\)").unwrap();
     let mut enabled = true;
     regex
@@ -173,6 +177,7 @@ pub fn part2_naive(input: &str) -> u32 {
 
 #[aoc(day3, part2, Opt)]
 pub fn part2_opt(s: &str) -> u32 {
+    let s = &*normalize_input(s);
     let input = s.as_bytes();
     let mut i = 0;
     let mut sum = 0;
@@ -226,6 +231,7 @@ unsafe fn match_do_dont(d_index: usize, input: &[u8]) -> (Option<bool>, usize) {
 
 #[aoc(day3, part2, Memchr)]
 pub fn part2_memchr(s: &str) -> u32 {
+    let s = &*normalize_input(s);
     const DO_LEN: usize = 4;
     const DONT_LEN: usize = 7;
 
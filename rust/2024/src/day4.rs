@@ -1,4 +1,5 @@
 use crate::utils::*;
+use anyhow::{ensure, Result};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Direction {
@@ -153,6 +154,7 @@ impl Letter {
 
 #[aoc(day4, part1, Naive)]
 fn part1_naive(s: &str) -> usize {
+    let s = &*normalize_input(s);
     let mut x_locs: Vec<(usize, usize)> = Vec::new();
     let letter_grid: Vec<Vec<Letter>> = s
         .lines()
@@ -180,6 +182,7 @@ fn part1_naive(s: &str) -> usize {
 
 #[aoc(day4, part2, Naive)]
 fn part2_naive(s: &str) -> usize {
+    let s = &*normalize_input(s);
     let mut a_locs: Vec<(usize, usize)> = Vec::new();
     let letter_grid: Vec<Vec<Letter>> = s
         .lines()
@@ -208,6 +211,8 @@ fn part2_naive(s: &str) -> usize {
 
 const PART1_WIDTH: usize = 32;
 
+/// # Safety
+/// `s` must have passed [`validate`], and AVX2 must be available.
 #[target_feature(enable = "avx2,bmi1,bmi2,cmpxchg16b,lzcnt,movbe,popcnt")]
 unsafe fn part1_simd(s: &str) -> usize {
     let input = s.as_bytes();
@@ -217,6 +222,7 @@ unsafe fn part1_simd(s: &str) -> usize {
         .enumerate()
         .filter_map(|(idx, b)| (b == b'\n').then_some(idx))
         .next()
+        .or(Some(input.len()))
         .filter(|&idx| idx != 0)
     else {
         return 0;
@@ -444,7 +450,7 @@ const XMAS: u32 = 0x58_4D_41_53_u32;
 unsafe fn check_remainder_slog(x_row: &[u8], m_row: &[u8], a_row: &[u8], s_row: &[u8]) -> usize {
     unsafe {
         std::hint::assert_unchecked(
-            x_row.len() < PART1_WIDTH
+            x_row.len() <= PART1_WIDTH
                 && x_row.len() == m_row.len()
                 && x_row.len() == a_row.len()
                 && x_row.len() == s_row.len(),
@@ -498,6 +504,8 @@ const M: u8 = b'M';
 const A: u8 = b'A';
 const S: u8 = b'S';
 
+/// # Safety
+/// `s` must have passed [`validate`], and AVX2 must be available.
 #[target_feature(enable = "avx2,bmi1,bmi2,cmpxchg16b,lzcnt,movbe,popcnt")]
 unsafe fn part2_simd(s: &str) -> usize {
     let input = s.as_bytes();
@@ -523,7 +531,7 @@ unsafe fn part2_simd(s: &str) -> usize {
     let bottom_right_offset = 2 * stride + 2;
     let mut top_left_offset = 0;
 
-    while top_left_offset < end_bound - PART2_FAST {
+    while top_left_offset < end_bound.saturating_sub(PART2_FAST) {
         sum += unsafe {
             part2_fast_check(
                 input
@@ -614,14 +622,37 @@ fn arr_diff<const N: usize>(a: &[u8; N], b: &[u8; N]) -> [u8; N] {
     out
 }
 
+/// Checks that `input` is a rectangular grid of `XMAS` letters, since the SIMD paths find the
+/// neighbouring rows by a fixed stride and index them without bounds checks.
+fn validate(input: &str) -> Result<()> {
+    let width = input.find('\n').unwrap_or(input.len());
+    ensure!(width > 0, "The first row is empty");
+    validate_lines(input, |_, line| {
+        check_line_shape(line, width, |_, byte| {
+            matches!(byte, b'X' | b'M' | b'A' | b'S')
+        })
+    })
+}
+
+/// Validates `input` before handing it to one of the SIMD paths, which require AVX2 and index
+/// the input without bounds checks.
+fn run_simd(input: &str, fast_path: unsafe fn(&str) -> usize) -> Result<usize> {
+    ensure!(is_x86_feature_detected!("avx2"), "AVX2 is not available");
+    validate(input)?;
+    // SAFETY: validated above.
+    Ok(unsafe { fast_path(input) })
+}
+
 #[aoc(day4, part1, Simd)]
-pub fn part1(s: &str) -> usize {
-    unsafe { part1_simd(s) }
+pub fn part1(s: &str) -> Result<usize> {
+    let s = &*normalize_input(s);
+    run_simd(s, part1_simd)
 }
 
 #[aoc(day4, part2, Simd)]
-pub fn part2(s: &str) -> usize {
-    unsafe { part2_simd(s) }
+pub fn part2(s: &str) -> Result<usize> {
+    let s = &*normalize_input(s);
+    run_simd(s, part2_simd)
 }
 
 #[cfg(test)]
@@ -667,21 +698,58 @@ MXMXAXMASX";
 
     #[test]
     fn test_part1_simd_site() {
-        assert_eq!(unsafe { part1_simd(SITE_INPUT) }, SITE_PART1_EXPECTED);
+        assert_eq!(part1(SITE_INPUT).unwrap(), SITE_PART1_EXPECTED);
     }
 
     #[test]
     fn test_part2_simd_site() {
-        assert_eq!(unsafe { part2_simd(SITE_INPUT) }, SITE_PART2_EXPECTED);
+        assert_eq!(part2(SITE_INPUT).unwrap(), SITE_PART2_EXPECTED);
     }
 
     #[test]
     fn test_part1_simd_mine() {
-        assert_eq!(unsafe { part1_simd(MY_INPUT) }, MY_PART1_EXPECTED);
+        assert_eq!(part1(MY_INPUT).unwrap(), MY_PART1_EXPECTED);
     }
 
     #[test]
     fn test_part2_simd_mine() {
-        assert_eq!(unsafe { part2_simd(MY_INPUT) }, MY_PART2_EXPECTED);
+        assert_eq!(part2(MY_INPUT).unwrap(), MY_PART2_EXPECTED);
+    }
+
+    #[test]
+    fn test_simd_validates() {
+        assert!(part1("").is_err());
+        assert!(part1("XMAS\nXMA\n").is_err());
+        assert!(part2("XMAS\nXMAQ\n").is_err());
+        assert!(part2("XMAS \nXMAS \n").is_err());
+        assert_eq!(
+            part1(&SITE_INPUT.replace('\n', "\r\n")).unwrap(),
+            SITE_PART1_EXPECTED
+        );
+        assert_eq!(
+            part2(&format!("\u{feff}{SITE_INPUT}\n")).unwrap(),
+            SITE_PART2_EXPECTED
+        );
+    }
+
+    #[test]
+    fn test_simd_matches_naive() {
+        let mut next = xorshift(0x9e37_79b9_7f4a_7c15_u64);
+        for _ in 0..300 {
+            let (rows, columns) = (next(12) + 1, next(40) + 1);
+            let mut grid = (0..rows)
+                .map(|_| {
+                    (0..columns)
+                        .map(|_| ['X', 'M', 'A', 'S'][next(4) as usize])
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            if next(2) == 0 {
+                grid.push('\n');
+            }
+            assert_eq!(part1(&grid).unwrap(), part1_naive(&grid), "{grid}");
+            assert_eq!(part2(&grid).unwrap(), part2_naive(&grid), "{grid}");
+        }
     }
 }
@@ -1,7 +1,7 @@
 use super::*;
 use arrayvec::ArrayVec;
 
-use anyhow::{Error, Result};
+use anyhow::{ensure, Error, Result};
 use nom::{
     bytes::complete::{tag, take},
     character::complete::newline,
@@ -12,18 +12,72 @@ use nom::{
     Finish, IResult, Parser,
 };
 
+/// Checks the layout the SIMD paths index into without bounds checks: `ab|cd` rules, a blank line,
+/// then at least one update of an odd number of two-digit pages, between 3 and 31, separated by
+/// commas.
+fn validate(input: &str) -> Result<()> {
+    let mut in_updates = false;
+    let mut updates = 0;
+    validate_lines(input, |_, line| {
+        if !in_updates {
+            if line.is_empty() {
+                in_updates = true;
+                return Ok(());
+            }
+            return check_line_shape(line, 5, |col, byte| {
+                if col == 2 {
+                    byte == b'|'
+                } else {
+                    byte.is_ascii_digit()
+                }
+            });
+        }
+        updates += 1;
+        let pages = (line.len() + 1) / 3;
+        ensure!(
+            pages % 2 == 1 && (3..=31).contains(&pages),
+            "Expected an odd number of pages from 3 to 31, found {pages}"
+        );
+        check_line_shape(line, pages * 3 - 1, |col, byte| {
+            if col % 3 == 2 {
+                byte == b','
+            } else {
+                byte.is_ascii_digit()
+            }
+        })
+    })?;
+    ensure!(
+        in_updates,
+        "Missing blank line between the rules and the updates"
+    );
+    ensure!(updates > 0, "Expected at least one update");
+    Ok(())
+}
+
+/// Validates `input` before handing it to one of the SIMD paths, which require AVX2 and index
+/// the input without bounds checks.
+fn run_simd(input: &str, fast_path: unsafe fn(&[u8]) -> u32) -> Result<u32> {
+    ensure!(is_x86_feature_detected!("avx2"), "AVX2 is not available");
+    validate(input)?;
+    // SAFETY: validated above.
+    Ok(unsafe { fast_path(input.as_bytes()) })
+}
+
 #[aoc(day5, part1, Simd)]
-fn part1_simd_wrapper(input: &str) -> u32 {
-    unsafe { part1_simd(input.as_bytes()) }
+fn part1_simd_wrapper(input: &str) -> Result<u32> {
+    let input = &*normalize_input(input);
+    run_simd(input, part1_simd)
 }
 
 #[aoc(day5, part2, Simd)]
-fn part2_simd_wrapper(input: &str) -> u32 {
-    unsafe { part2_simd(input.as_bytes()) }
+fn part2_simd_wrapper(input: &str) -> Result<u32> {
+    let input = &*normalize_input(input);
+    run_simd(input, part2_simd)
 }
 
 #[aoc(day5, part1, Base)]
 fn part1_base(s: &str) -> Result<u32> {
+    let s = &*normalize_input(s);
     let mut lut = [false; 100 * 100];
 
     let parse_mapping = separated_pair(parse_digit_pair, tag("|"), parse_digit_pair);
@@ -44,7 +98,7 @@ fn part1_base(s: &str) -> Result<u32> {
 
     assert!(lut.iter().copied().any(|x| x));
 
-    fold_many1(
+    let total = fold_many1(
         terminated(separated_list1(tag(","), parse_digit_pair), opt(tag("\n"))),
         || 0_u32,
         move |acc, list| {
@@ -65,11 +119,13 @@ fn part1_base(s: &str) -> Result<u32> {
     .parse_complete(rest)
     .finish()
     .map(|(_, x)| x)
-    .map_err(|e| Error::msg(format!("Failed to parse input:\n{e:#?}")))
+    .map_err(|e| Error::msg(format!("Failed to parse input:\n{e:#?}")));
+    total
 }
 
 #[aoc(day5, part2, Base)]
 fn part2_base(s: &str) -> Result<u32> {
+    let s = &*normalize_input(s);
     let mut lut = [false; 100 * 100];
 
     let parse_mapping = separated_pair(parse_digit_pair, tag("|"), parse_digit_pair);
@@ -90,7 +146,7 @@ fn part2_base(s: &str) -> Result<u32> {
 
     assert!(lut.iter().copied().any(|x| x));
 
-    fold_many1(
+    let total = fold_many1(
         terminated(separated_list1(tag(","), parse_digit_pair), opt(tag("\n"))),
         || 0_u32,
         move |acc, mut list| {
@@ -126,7 +182,8 @@ fn part2_base(s: &str) -> Result<u32> {
     .parse_complete(rest)
     .finish()
     .map(|(_, x)| x)
-    .map_err(|e| Error::msg(format!("Failed to parse input:\n{e:#?}")))
+    .map_err(|e| Error::msg(format!("Failed to parse input:\n{e:#?}")));
+    total
 }
 
 fn parse_digit_pair(input: &[u8]) -> IResult<&[u8], u8, VerboseError<&[u8]>> {
@@ -135,8 +192,9 @@ fn parse_digit_pair(input: &[u8]) -> IResult<&[u8], u8, VerboseError<&[u8]>> {
         .parse(input)
 }
 
-pub fn part1(s: &str) -> u32 {
-    unsafe { part1_simd(s.as_bytes()) }
+pub fn part1(s: &str) -> Result<u32> {
+    let s = &*normalize_input(s);
+    run_simd(s, part1_simd)
 }
 
 pub fn part2(s: &str) -> Result<u32> {
@@ -144,7 +202,7 @@ pub fn part2(s: &str) -> Result<u32> {
 }
 
 /// # Safety
-/// Can only be called on x86 systems with certain instructions available.
+/// `input` must have passed [`validate`], and AVX2 must be available.
 #[target_feature(enable = "avx2,bmi1,bmi2,cmpxchg16b,lzcnt,movbe,popcnt")]
 pub unsafe fn part1_simd(input: &[u8]) -> u32 {
     // Bitsets, given a, b, there's rule a|b iff a_must_before_b[a] & (1<<b) != 0
@@ -211,7 +269,7 @@ pub unsafe fn part1_simd(input: &[u8]) -> u32 {
 }
 
 /// # Safety
-/// Can only be called on x86 systems with certain instructions available.
+/// `input` must have passed [`validate`], and AVX2 must be available.
 #[target_feature(enable = "avx2,bmi1,bmi2,cmpxchg16b,lzcnt,movbe,popcnt")]
 pub unsafe fn part2_simd(input: &[u8]) -> u32 {
     // Bitsets, given a, b, there's rule a|b iff a_must_before_b[a] & (1<<b) != 0
@@ -354,30 +412,39 @@ mod test {
 
     #[test]
     fn test_part1_simd_mine() {
-        unsafe {
-            assert_eq!(part1_simd(MY_INPUT.as_bytes()), MY_PART1_OUTPUT);
-        }
+        assert_eq!(part1_simd_wrapper(MY_INPUT).unwrap(), MY_PART1_OUTPUT);
     }
 
     #[test]
     fn test_part1_simd_site() {
-        unsafe {
-            assert_eq!(part1_simd(SITE_INPUT.as_bytes()), SITE_PART1_OUTPUT);
-        }
+        assert_eq!(part1_simd_wrapper(SITE_INPUT).unwrap(), SITE_PART1_OUTPUT);
     }
 
     #[test]
     fn test_part2_simd_mine() {
-        unsafe {
-            assert_eq!(part2_simd(MY_INPUT.as_bytes()), MY_PART1_OUTPUT);
-        }
+        assert_eq!(part2_simd_wrapper(MY_INPUT).unwrap(), MY_PART1_OUTPUT);
     }
 
     #[test]
     fn test_part2_simd_site() {
-        unsafe {
-            assert_eq!(part2_simd(SITE_INPUT.as_bytes()), SITE_PART2_OUTPUT);
-        }
+        assert_eq!(part2_simd_wrapper(SITE_INPUT).unwrap(), SITE_PART2_OUTPUT);
+    }
+
+    #[test]
+    fn test_simd_rejects_malformed_input() {
+        assert!(part1_simd_wrapper("").is_err());
+        assert!(part1_simd_wrapper("47|53\n").is_err());
+        assert!(part1_simd_wrapper("47|53\n\n").is_err());
+        assert!(part2_simd_wrapper("47|53\n\n75,47\n").is_err());
+        assert!(part2_simd_wrapper("47|53\n\n75,47,61 \n").is_err());
+        assert!(part1_simd_wrapper("47|5\n\n75,47,61\n").is_err());
+        let long = vec!["10"; 33].join(",");
+        assert!(part2_simd_wrapper(&format!("47|53\n\n{long}\n")).is_err());
+        assert_eq!(part1_simd_wrapper("47|53\n\n47,53,61\n").unwrap(), 53);
+        assert_eq!(
+            part1_simd_wrapper(&SITE_INPUT.replace('\n', "\r\n")).unwrap(),
+            SITE_PART1_OUTPUT
+        );
     }
 
     #[test]
@@ -1,6 +1,7 @@
 use rustc_hash::FxHashSet;
 
 use super::*;
+use anyhow::{bail, ensure};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
@@ -35,6 +36,7 @@ impl From<Direction> for (isize, isize) {
 
 #[aoc(day6, part1)]
 fn part1_basic(s: &str) -> usize {
+    let s = &*normalize_input(s);
     let mut obstacles = FxHashSet::default();
     let mut guard_start = None;
 
@@ -78,6 +80,7 @@ fn part1_basic(s: &str) -> usize {
 
 #[aoc(day6, part2)]
 fn part2_basic(s: &str) -> usize {
+    let s = &*normalize_input(s);
     let mut obstacles = FxHashSet::default();
     let mut guard_start = None;
 
@@ -136,6 +139,184 @@ fn part2_basic(s: &str) -> usize {
     potential_obstacles
 }
 
+/// The lab's floor plan, with how far the guard walks from each cell in each direction before
+/// bumping into an obstacle or stepping off the map.
+struct Lab {
+    rows: usize,
+    cols: usize,
+    start: usize,
+    obstacles: Vec<bool>,
+    reach: Vec<[usize; 4]>,
+}
+
+impl Lab {
+    fn parse(s: &str) -> Result<Self> {
+        let rows = s.lines().count();
+        let cols = s.find('\n').unwrap_or(s.len());
+        let mut obstacles = vec![false; rows * cols];
+        let mut start = None;
+        // Both come out sorted, since the map is read in row-major order.
+        let mut row_obstacles = vec![Vec::new(); rows];
+        let mut col_obstacles = vec![Vec::new(); cols];
+        for (row, line) in s.lines().enumerate() {
+            ensure!(line.len() == cols, "Ragged line {line:?}");
+            for (col, b) in line.bytes().enumerate() {
+                match b {
+                    b'#' => {
+                        obstacles[row * cols + col] = true;
+                        row_obstacles[row].push(col);
+                        col_obstacles[col].push(row);
+                    }
+                    b'^' => {
+                        ensure!(start.is_none(), "More than one guard");
+                        start = Some(row * cols + col);
+                    }
+                    b'.' => (),
+                    _ => bail!("Unexpected byte {:?}", b as char),
+                }
+            }
+        }
+        let start = start.context("No guard")?;
+
+        let mut reach = Vec::with_capacity(rows * cols);
+        for (row, in_row) in row_obstacles.iter().enumerate() {
+            for (col, in_col) in col_obstacles.iter().enumerate() {
+                let above = in_col.partition_point(|&r| r < row);
+                let below = in_col.partition_point(|&r| r <= row);
+                let left = in_row.partition_point(|&c| c < col);
+                let right = in_row.partition_point(|&c| c <= col);
+                let mut jumps = [0; 4];
+                jumps[Direction::Up as usize] =
+                    above.checked_sub(1).map_or(row + 1, |i| row - in_col[i]);
+                jumps[Direction::Down as usize] =
+                    in_col.get(below).map_or(rows - row, |&r| r - row);
+                jumps[Direction::Left as usize] =
+                    left.checked_sub(1).map_or(col + 1, |i| col - in_row[i]);
+                jumps[Direction::Right as usize] =
+                    in_row.get(right).map_or(cols - col, |&c| c - col);
+                reach.push(jumps);
+            }
+        }
+
+        Ok(Lab {
+            rows,
+            cols,
+            start,
+            obstacles,
+            reach,
+        })
+    }
+
+    /// The cell `steps` away from `cell` heading in `dir`, or `None` if that's off the map.
+    fn walk(&self, cell: usize, dir: Direction, steps: usize) -> Option<usize> {
+        let (dr, dc) = <(isize, isize)>::from(dir);
+        let row = (cell / self.cols).wrapping_add_signed(dr * steps as isize);
+        let col = (cell % self.cols).wrapping_add_signed(dc * steps as isize);
+        (row < self.rows && col < self.cols).then(|| row * self.cols + col)
+    }
+
+    /// How many steps from `cell` heading in `dir` it is to `obstacle`, if it's straight ahead.
+    fn distance_ahead(&self, cell: usize, dir: Direction, obstacle: usize) -> Option<usize> {
+        let (row, col) = (cell / self.cols, cell % self.cols);
+        let (o_row, o_col) = (obstacle / self.cols, obstacle % self.cols);
+        match dir {
+            Direction::Up if o_col == col && o_row < row => Some(row - o_row),
+            Direction::Down if o_col == col && o_row > row => Some(o_row - row),
+            Direction::Left if o_row == row && o_col < col => Some(col - o_col),
+            Direction::Right if o_row == row && o_col > col => Some(o_col - col),
+            _ => None,
+        }
+    }
+
+    /// Whether the guard, standing on `cell` facing `dir`, ends up going round in circles once
+    /// `obstacle` is added to the map. Only the cells where the guard turns are recorded, since a
+    /// loop has to pass through one of them.
+    fn loops_with(
+        &self,
+        obstacle: usize,
+        mut cell: usize,
+        mut dir: Direction,
+        turns: &mut VisitedSet,
+    ) -> bool {
+        turns.clear();
+        loop {
+            let mut steps = self.reach[cell][dir as usize];
+            if let Some(distance) = self.distance_ahead(cell, dir, obstacle) {
+                steps = steps.min(distance);
+            }
+            if self.walk(cell, dir, steps).is_none() {
+                return false;
+            }
+            cell = self.walk(cell, dir, steps - 1).unwrap();
+            dir = dir.turn_right();
+            if !turns.insert(cell * 4 + dir as usize) {
+                return true;
+            }
+        }
+    }
+}
+
+/// A dense bitset that remembers which words it has touched, so clearing it between trials only
+/// costs as much as the trial did.
+struct VisitedSet {
+    words: Vec<u64>,
+    touched: Vec<usize>,
+}
+
+impl VisitedSet {
+    fn new(len: usize) -> Self {
+        VisitedSet {
+            words: vec![0; len.div_ceil(64)],
+            touched: Vec::new(),
+        }
+    }
+
+    /// Returns whether `index` wasn't already in the set.
+    fn insert(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / 64, 1 << (index % 64));
+        if self.words[word] & bit != 0 {
+            return false;
+        }
+        if self.words[word] == 0 {
+            self.touched.push(word);
+        }
+        self.words[word] |= bit;
+        true
+    }
+
+    fn clear(&mut self) {
+        for word in self.touched.drain(..) {
+            self.words[word] = 0;
+        }
+    }
+}
+
+/// Only cells on the guard's original route can change where it goes, and it only reaches each one
+/// the first time the original route does, so each trial starts from just before that point.
+#[aoc(day6, part2, JumpTable)]
+fn part2_jump_table(s: &str) -> Result<usize> {
+    let s = &*normalize_input(s);
+    let lab = Lab::parse(s)?;
+    let cells = lab.rows * lab.cols;
+    let mut tried = vec![false; cells];
+    tried[lab.start] = true;
+    let mut turns = VisitedSet::new(cells * 4);
+    let mut potential_obstacles = 0;
+    let (mut cell, mut dir) = (lab.start, Direction::Up);
+    while let Some(next) = lab.walk(cell, dir, 1) {
+        if lab.obstacles[next] {
+            dir = dir.turn_right();
+            continue;
+        }
+        if !tried[next] {
+            tried[next] = true;
+            potential_obstacles += lab.loops_with(next, cell, dir, &mut turns) as usize;
+        }
+        cell = next;
+    }
+    Ok(potential_obstacles)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -163,4 +344,58 @@ mod test {
     fn test_part2_basic() {
         assert_eq!(part2_basic(SITE_INPUT), SITE_PART2_ANSWER);
     }
+
+    #[test]
+    fn test_part2_jump_table() {
+        assert_eq!(part2_jump_table(SITE_INPUT).unwrap(), SITE_PART2_ANSWER);
+    }
+
+    #[test]
+    fn test_lab_parse() {
+        assert_eq!(part2_jump_table("..^.#").unwrap(), 0);
+        assert!(Lab::parse("..^\n...\n..").is_err());
+        assert!(Lab::parse("...\n...").is_err());
+        assert!(Lab::parse(".^.\n.^.").is_err());
+        assert!(Lab::parse(".^.\n.x.").is_err());
+    }
+
+    #[test]
+    fn test_jump_table_matches_basic() {
+        let mut next = xorshift(0x9e37_79b9_7f4a_7c15_u64);
+        for _ in 0..200 {
+            let (rows, cols) = (6 + next(15) as usize, 6 + next(15) as usize);
+            let density = 4 + next(10);
+            let mut map = String::new();
+            let start = (next(rows as u64) as usize, next(cols as u64) as usize);
+            for row in 0..rows {
+                for col in 0..cols {
+                    map.push(if (row, col) == start {
+                        '^'
+                    } else if next(density) == 0 {
+                        '#'
+                    } else {
+                        '.'
+                    });
+                }
+                map.push('\n');
+            }
+            // The puzzle promises the guard leaves the map to begin with, and adding an obstacle
+            // that's already there doesn't change anything.
+            let lab = Lab::parse(&map).unwrap();
+            let mut turns = VisitedSet::new(rows * cols * 4);
+            let obstacle = (0..rows * cols).find(|&cell| lab.obstacles[cell]);
+            if obstacle.is_some_and(|o| lab.loops_with(o, lab.start, Direction::Up, &mut turns)) {
+                continue;
+            }
+            assert_eq!(part2_jump_table(&map).unwrap(), part2_basic(&map), "{map}");
+        }
+    }
+
+    #[test]
+    fn test_windows_line_endings() {
+        let windows = format!("\u{feff}{}", SITE_INPUT.replace('\n', "\r\n"));
+        assert_eq!(part1_basic(&windows), SITE_PART1_ANSWER);
+        assert_eq!(part2_basic(&windows), SITE_PART2_ANSWER);
+        assert_eq!(part2_jump_table(&windows).unwrap(), SITE_PART2_ANSWER);
+    }
 }
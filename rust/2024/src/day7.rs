@@ -11,6 +11,7 @@ fn parse_line(line: &str) -> StrIResult<(u64, Vec<u64>)> {
 
 #[aoc(day7, part1)]
 fn part1(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     s.lines()
         //.par_bridge()
         .map(|line| run_parse(line, parse_line).unwrap())
@@ -45,6 +46,7 @@ fn concat(a: u64, b: u64) -> u64 {
 
 #[aoc(day7, part2)]
 fn part2(s: &str) -> u64 {
+    let s = &*normalize_input(s);
     s.lines()
         .par_bridge()
         .map(|line| run_parse(line, parse_line).unwrap())
@@ -5,6 +5,7 @@ type ParsedInput = ([Vec<(isize, isize)>; 62], (isize, isize));
 
 #[aoc_generator(day8)]
 fn build_map(s: &str) -> ParsedInput {
+    let s = &*normalize_input(s);
     let columns = s.find("\n").unwrap();
     let stride = columns + 1;
     let rows = (s.len() + 1) / stride;
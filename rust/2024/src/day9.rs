@@ -1,9 +1,11 @@
-use std::{cmp::Reverse, collections::BinaryHeap};
+use std::{cmp::Reverse, collections::BinaryHeap, fmt};
 
 use super::*;
+use anyhow::bail;
 
 #[aoc(day9, part1)]
 pub fn part1(input: &str) -> u64 {
+    let input = &*normalize_input(input);
     let mut bytes = input.trim_ascii_end().as_bytes();
     let mut block_idx = 0;
     let mut spaces_remaining = 0;
@@ -38,7 +40,8 @@ pub fn part1(input: &str) -> u64 {
         let moved_width = blocks_remaining.min(spaces_remaining);
         checksum += unsafe { calc_checksum_increment(right_file_id, moved_width, block_idx) };
         blocks_remaining -= moved_width;
-        block_idx += moved_width.max(spaces_remaining) as u64;
+        // Whatever space is left gets filled by the last file sliding left into it.
+        block_idx += moved_width as u64;
     }
     let left_width = bytes[0] - b'0';
     checksum += unsafe { calc_checksum_increment(left_file_id, left_width, block_idx) };
@@ -74,6 +77,7 @@ struct Gap {
 
 #[aoc(day9, part2)]
 pub fn part2(input: &str) -> u64 {
+    let input = &*normalize_input(input);
     let bytes = input.trim_ascii_end().as_bytes();
     let mut block_idx = 0;
     let mut gaps: Vec<Gap> = Vec::with_capacity(bytes.len() / 2);
@@ -130,3 +134,186 @@ pub fn part2(input: &str) -> u64 {
 
     checksum
 }
+
+/// The disk block by block, with `None` for free space.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disk {
+    blocks: Vec<Option<u32>>,
+}
+
+impl Disk {
+    /// Parses the dense map, where digits alternate between file lengths and gap lengths.
+    pub fn parse(map: &str) -> Result<Self> {
+        let mut blocks = Vec::new();
+        for (idx, b) in map.trim_ascii_end().bytes().enumerate() {
+            if !b.is_ascii_digit() {
+                bail!("Unexpected byte {:?} in the disk map", b as char);
+            }
+            let block = (idx % 2 == 0).then_some(idx as u32 / 2);
+            blocks.extend(std::iter::repeat_n(block, (b - b'0') as usize));
+        }
+        Ok(Disk { blocks })
+    }
+
+    pub fn blocks(&self) -> &[Option<u32>] {
+        &self.blocks
+    }
+
+    /// Moves file blocks one at a time from the end of the disk into the leftmost free block,
+    /// until there are no gaps left between them.
+    pub fn compact_blocks(&mut self) {
+        let (mut free, mut last) = (0, self.blocks.len());
+        loop {
+            while free < last && self.blocks[free].is_some() {
+                free += 1;
+            }
+            while last > free && self.blocks[last - 1].is_none() {
+                last -= 1;
+            }
+            if last - free < 2 {
+                break;
+            }
+            self.blocks.swap(free, last - 1);
+        }
+    }
+
+    /// Tries to move each whole file once, highest id first, into the leftmost run of free blocks
+    /// before it that's big enough. A file split up by [`compact_blocks`](Self::compact_blocks)
+    /// has each of its pieces moved separately, rightmost first.
+    pub fn compact_files(&mut self) {
+        let mut files = Vec::new();
+        // Free runs by length, capped at 9 since that's as long as a file can be. Within each
+        // length the leftmost run comes first.
+        let mut gaps: [BinaryHeap<Reverse<(usize, usize)>>; 10] = [const { BinaryHeap::new() }; 10];
+        let mut start = 0;
+        for (idx, block) in self.blocks.iter().enumerate() {
+            if idx + 1 == self.blocks.len() || self.blocks[idx + 1] != *block {
+                let len = idx + 1 - start;
+                match block {
+                    Some(id) => files.push((*id, start, len)),
+                    None => gaps[len.min(9)].push(Reverse((start, len))),
+                }
+                start = idx + 1;
+            }
+        }
+        files.sort_unstable_by_key(|&(id, start, _)| Reverse((id, start)));
+
+        for (id, start, len) in files {
+            let Some(bucket) = (len.min(9)..10)
+                .filter(|&bucket| gaps[bucket].peek().is_some_and(|gap| gap.0 .0 < start))
+                .min_by_key(|&bucket| gaps[bucket].peek().unwrap().0 .0)
+            else {
+                continue;
+            };
+            let Reverse((gap_start, gap_len)) = gaps[bucket].pop().unwrap();
+            if gap_len < len {
+                // Only possible for files longer than 9 blocks, which a dense map can't describe.
+                gaps[bucket].push(Reverse((gap_start, gap_len)));
+                continue;
+            }
+            self.blocks[gap_start..gap_start + len].fill(Some(id));
+            self.blocks[start..start + len].fill(None);
+            if gap_len > len {
+                let rest = gap_len - len;
+                gaps[rest.min(9)].push(Reverse((gap_start + len, rest)));
+            }
+        }
+    }
+
+    pub fn checksum(&self) -> u64 {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, block)| block.map(|id| idx as u64 * id as u64))
+            .sum()
+    }
+}
+
+/// Renders the layout the way the puzzle does, with `.` for free blocks. Ids past 9 are written
+/// in brackets so they can't be mistaken for several blocks.
+impl fmt::Display for Disk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for block in &self.blocks {
+            match block {
+                None => write!(f, ".")?,
+                Some(id @ 0..=9) => write!(f, "{id}")?,
+                Some(id) => write!(f, "[{id}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[aoc(day9, part1, Disk)]
+pub fn part1_disk(input: &str) -> Result<u64> {
+    let input = &*normalize_input(input);
+    let mut disk = Disk::parse(input)?;
+    disk.compact_blocks();
+    Ok(disk.checksum())
+}
+
+#[aoc(day9, part2, Disk)]
+pub fn part2_disk(input: &str) -> Result<u64> {
+    let input = &*normalize_input(input);
+    let mut disk = Disk::parse(input)?;
+    disk.compact_files();
+    Ok(disk.checksum())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static SITE_INPUT: &str = "2333133121414131402\n";
+
+    #[test]
+    fn test_disk() -> Result<()> {
+        let disk = Disk::parse(SITE_INPUT)?;
+        assert_eq!(
+            disk.to_string(),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+        let mut blocks = disk.clone();
+        blocks.compact_blocks();
+        assert_eq!(
+            blocks.to_string(),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(blocks.checksum(), 1928);
+        let mut files = disk.clone();
+        files.compact_files();
+        assert_eq!(
+            files.to_string(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(files.checksum(), 2858);
+
+        assert_eq!(part1(SITE_INPUT), 1928);
+        assert_eq!(part2(SITE_INPUT), 2858);
+        assert_eq!(part2_disk("\u{feff}2333133121414131402\r\n")?, 2858);
+        assert_eq!(
+            Disk::parse("1910101010101010101010101")?.to_string(),
+            "0.........123456789[10][11][12]"
+        );
+        assert!(Disk::parse("12a").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_paths_match_disk() -> Result<()> {
+        let mut next = xorshift(0x9e37_79b9_7f4a_7c15_u64);
+        for _ in 0..200 {
+            let files = 2 + next(40);
+            let mut map = String::new();
+            for file in 0..files {
+                if file > 0 {
+                    map.push((b'0' + next(10) as u8) as char);
+                }
+                map.push((b'1' + next(9) as u8) as char);
+            }
+            assert_eq!(part1(&map), part1_disk(&map)?, "{map}");
+            assert_eq!(part2(&map), part2_disk(&map)?, "{map}");
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
 use aoc_runner_derive::aoc_lib;
 
+pub mod clique;
+pub mod linear;
+pub mod memo;
+pub mod sat;
 pub mod utils;
 
 pub mod day1;
@@ -0,0 +1,238 @@
+//! Exact integer linear algebra: square systems solved by fraction-free (Bareiss) elimination, and
+//! the cheapest non-negative solution of a single two-variable equation by extended Euclid. All
+//! arithmetic is checked `i128`, so overflow is an error rather than a wrong answer.
+
+use anyhow::{ensure, Context, Result};
+
+fn checked(value: Option<i128>) -> Result<i128> {
+    value.context("Overflow in linear solver")
+}
+
+/// Returns `(g, x, y)` with `g = gcd(a, b) >= 0` and `a * x + b * y = g`.
+pub fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    if old_r < 0 {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+/// The unique solution of a square system, as numerators over a common positive denominator in
+/// lowest terms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    pub numerators: Vec<i128>,
+    pub denominator: i128,
+}
+
+impl Solution {
+    /// The solution if every component is a whole number.
+    pub fn integers(&self) -> Option<Vec<i128>> {
+        (self.denominator == 1).then(|| self.numerators.clone())
+    }
+}
+
+/// Solves `matrix * x = rhs` exactly, or returns `None` if `matrix` is singular.
+pub fn solve(matrix: &[Vec<i128>], rhs: &[i128]) -> Result<Option<Solution>> {
+    let n = rhs.len();
+    ensure!(
+        matrix.len() == n && matrix.iter().all(|row| row.len() == n),
+        "Expected a {n}x{n} matrix"
+    );
+    let mut rows: Vec<Vec<i128>> = matrix
+        .iter()
+        .zip(rhs)
+        .map(|(row, &value)| row.iter().copied().chain([value]).collect())
+        .collect();
+    // Bareiss elimination: every entry stays a minor of the original matrix, so the division by the
+    // previous pivot is always exact and nothing grows beyond the determinant's size.
+    let mut previous = 1;
+    for k in 0..n {
+        let Some(pivot) = (k..n).find(|&row| rows[row][k] != 0) else {
+            return Ok(None);
+        };
+        rows.swap(k, pivot);
+        for i in k + 1..n {
+            for j in k + 1..=n {
+                let kept = checked(rows[i][j].checked_mul(rows[k][k]))?;
+                let removed = checked(rows[i][k].checked_mul(rows[k][j]))?;
+                rows[i][j] = checked(kept.checked_sub(removed))? / previous;
+            }
+            rows[i][k] = 0;
+        }
+        previous = rows[k][k];
+    }
+    // The last pivot is the determinant up to sign, and by Cramer's rule it times each unknown is a
+    // whole number, so back substitution stays exact too.
+    let determinant = if n == 0 { 1 } else { rows[n - 1][n - 1] };
+    let mut numerators = vec![0; n];
+    for i in (0..n).rev() {
+        let mut value = checked(rows[i][n].checked_mul(determinant))?;
+        for j in i + 1..n {
+            value = checked(value.checked_sub(checked(rows[i][j].checked_mul(numerators[j]))?))?;
+        }
+        numerators[i] = value / rows[i][i];
+    }
+    let sign = determinant.signum();
+    let divisor = numerators
+        .iter()
+        .fold(determinant, |g, &value| ext_gcd(g, value).0);
+    Ok(Some(Solution {
+        numerators: numerators
+            .into_iter()
+            .map(|value| sign * value / divisor)
+            .collect(),
+        denominator: sign * determinant / divisor,
+    }))
+}
+
+/// The non-negative `(x, y)` with `a * x + b * y = target` that minimises
+/// `cost_a * x + cost_b * y`, if there is one. Everything must be non-negative, which keeps the
+/// solutions to a bounded stretch of a line; ties go to the smallest `x`.
+pub fn cheapest_combination(
+    a: i128,
+    b: i128,
+    target: i128,
+    cost_a: i128,
+    cost_b: i128,
+) -> Result<Option<(i128, i128)>> {
+    ensure!(
+        a >= 0 && b >= 0 && target >= 0 && cost_a >= 0 && cost_b >= 0,
+        "Expected non-negative coefficients, target and costs"
+    );
+    match (a, b) {
+        (0, 0) => return Ok((target == 0).then_some((0, 0))),
+        (0, _) => return Ok((target % b == 0).then(|| (0, target / b))),
+        (_, 0) => return Ok((target % a == 0).then(|| (target / a, 0))),
+        _ => {}
+    }
+    let (g, x, y) = ext_gcd(a, b);
+    if target % g != 0 {
+        return Ok(None);
+    }
+    // Every solution is (x0 + k * step_x, y0 - k * step_y) for some whole k.
+    let (x0, y0) = (
+        checked(x.checked_mul(target / g))?,
+        checked(y.checked_mul(target / g))?,
+    );
+    let (step_x, step_y) = (b / g, a / g);
+    let lowest = -x0.div_euclid(step_x);
+    let highest = y0.div_euclid(step_y);
+    if lowest > highest {
+        return Ok(None);
+    }
+    let slope = checked(
+        checked(cost_a.checked_mul(step_x))?.checked_sub(checked(cost_b.checked_mul(step_y))?),
+    )?;
+    let k = if slope >= 0 { lowest } else { highest };
+    Ok(Some((
+        checked(x0.checked_add(checked(k.checked_mul(step_x))?))?,
+        checked(y0.checked_sub(checked(k.checked_mul(step_y))?))?,
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::xorshift;
+
+    #[test]
+    fn test_ext_gcd() {
+        for (a, b) in [
+            (240, 46),
+            (-240, 46),
+            (0, 7),
+            (7, 0),
+            (0, 0),
+            (17, -5),
+            (1 << 100, 3),
+        ] {
+            let (g, x, y) = ext_gcd(a, b);
+            assert!(g >= 0);
+            assert_eq!(a * x + b * y, g);
+            if g != 0 {
+                assert_eq!((a % g, b % g), (0, 0));
+            }
+        }
+        assert_eq!(ext_gcd(240, 46).0, 2);
+    }
+
+    #[test]
+    fn test_solve() {
+        let matrix = [vec![2, 1, -1], vec![-3, -1, 2], vec![-2, 1, 2]];
+        let solution = solve(&matrix, &[8, -11, -3]).unwrap().unwrap();
+        assert_eq!(solution.integers(), Some(vec![2, 3, -1]));
+        let solution = solve(&[vec![0, 2], vec![3, 0]], &[1, 1]).unwrap().unwrap();
+        assert_eq!(
+            solution,
+            Solution {
+                numerators: vec![2, 3],
+                denominator: 6
+            }
+        );
+        assert_eq!(solution.integers(), None);
+        assert_eq!(solve(&[vec![1, 2], vec![2, 4]], &[3, 6]).unwrap(), None);
+        assert!(solve(&[vec![1, 2]], &[3]).is_err());
+        assert!(solve(&[vec![i128::MAX, 2], vec![3, i128::MAX]], &[1, 1]).is_err());
+        assert_eq!(
+            solve(&[], &[]).unwrap().unwrap().numerators,
+            Vec::<i128>::new()
+        );
+
+        let mut next = xorshift(0x2545_F491_4F6C_DD1D_u64);
+        for n in 1..6 {
+            for _ in 0..200 {
+                let matrix: Vec<Vec<i128>> = (0..n)
+                    .map(|_| (0..n).map(|_| next(21) as i128 - 10).collect())
+                    .collect();
+                let rhs: Vec<i128> = (0..n).map(|_| next(201) as i128 - 100).collect();
+                let Some(solution) = solve(&matrix, &rhs).unwrap() else {
+                    continue;
+                };
+                assert!(solution.denominator > 0);
+                for (row, &value) in matrix.iter().zip(&rhs) {
+                    let lhs: i128 = row
+                        .iter()
+                        .zip(&solution.numerators)
+                        .map(|(a, x)| a * x)
+                        .sum();
+                    assert_eq!(lhs, value * solution.denominator);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cheapest_combination() {
+        assert_eq!(cheapest_combination(1, 2, 5, 3, 1).unwrap(), Some((1, 2)));
+        assert_eq!(cheapest_combination(1, 2, 5, 1, 3).unwrap(), Some((5, 0)));
+        assert_eq!(cheapest_combination(4, 6, 5, 3, 1).unwrap(), None);
+        assert_eq!(cheapest_combination(0, 0, 0, 3, 1).unwrap(), Some((0, 0)));
+        assert_eq!(cheapest_combination(0, 4, 12, 3, 1).unwrap(), Some((0, 3)));
+        assert!(cheapest_combination(-1, 4, 12, 3, 1).is_err());
+        for a in 0..12 {
+            for b in 0..12 {
+                for target in 0..40 {
+                    let brute = (0..=40)
+                        .flat_map(|x| (0..=40).map(move |y| (x, y)))
+                        .filter(|(x, y)| a * x + b * y == target)
+                        .min_by_key(|&(x, y)| (3 * x + y, x));
+                    assert_eq!(
+                        cheapest_combination(a, b, target, 3, 1).unwrap(),
+                        brute,
+                        "{a}x + {b}y = {target}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Memoisation for recursive solutions, over a choice of backing store, either by plain recursion or
+//! by an explicit stack for recursions too deep for the real one.
+
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rustc_hash::FxHashMap;
+use std::{
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+/// Somewhere to keep the values a [`Memo`] has already worked out.
+pub trait Store<K, V> {
+    fn get(&mut self, key: &K) -> Option<V>;
+    fn insert(&mut self, key: K, value: V);
+}
+
+impl<K: Hash + Eq, V: Clone> Store<K, V> for FxHashMap<K, V> {
+    fn get(&mut self, key: &K) -> Option<V> {
+        FxHashMap::get(self, key).cloned()
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        FxHashMap::insert(self, key, value);
+    }
+}
+
+/// Lets threads share their work, each through its own [`Memo`] around a reference to the map.
+impl<K: Hash + Eq, V: Clone, S: BuildHasher + Clone> Store<K, V> for &DashMap<K, V, S> {
+    fn get(&mut self, key: &K) -> Option<V> {
+        DashMap::get(self, key).map(|value| value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        DashMap::insert(self, key, value);
+    }
+}
+
+/// Lets threads share their work through a single map, for when reads far outnumber writes.
+impl<K: Hash + Eq, V: Clone> Store<K, V> for &RwLock<FxHashMap<K, V>> {
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.read().get(key).cloned()
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.write().insert(key, value);
+    }
+}
+
+/// A store for keys that `index` maps onto `0..len`, which beats hashing when there aren't many.
+pub struct DenseStore<V, F> {
+    values: Vec<Option<V>>,
+    index: F,
+}
+
+impl<V: Clone, F> DenseStore<V, F> {
+    pub fn new(len: usize, index: F) -> Self {
+        DenseStore {
+            values: vec![None; len],
+            index,
+        }
+    }
+}
+
+impl<K, V: Clone, F: Fn(&K) -> usize> Store<K, V> for DenseStore<V, F> {
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.values[(self.index)(key)].clone()
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.values[(self.index)(&key)] = Some(value);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Lookups answered from the store.
+    pub hits: u64,
+    /// Values that had to be worked out.
+    pub misses: u64,
+}
+
+/// Caches a recursive function by its argument.
+pub struct Memo<K, V, S> {
+    store: S,
+    stats: Stats,
+    _function: PhantomData<fn(K) -> V>,
+}
+
+impl<K: Clone, V: Clone, S: Store<K, V>> Memo<K, V, S> {
+    pub fn new(store: S) -> Self {
+        Memo {
+            store,
+            stats: Stats::default(),
+            _function: PhantomData,
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    fn lookup(&mut self, key: &K) -> Option<V> {
+        let value = self.store.get(key);
+        self.stats.hits += value.is_some() as u64;
+        value
+    }
+
+    /// Calls `f` for `key` unless it's been worked out before. `f` recurses by calling back into
+    /// the memo, so the recursion is only as deep as the real stack allows.
+    pub fn call(&mut self, key: K, f: &impl Fn(&mut Self, &K) -> V) -> V {
+        if let Some(value) = self.lookup(&key) {
+            return value;
+        }
+        self.stats.misses += 1;
+        let value = f(self, &key);
+        self.store.insert(key, value.clone());
+        value
+    }
+
+    /// Works out `key` without recursing. Instead `f` asks [`Lookup::get`] for what it depends on
+    /// and gives up with `None` when something's missing; the missing value is worked out first,
+    /// then `f` is run again. `f` must not depend on its own key, directly or otherwise, or this
+    /// never finishes.
+    pub fn evaluate(
+        &mut self,
+        key: K,
+        mut f: impl FnMut(&K, &mut Lookup<'_, K, V, S>) -> Option<V>,
+    ) -> V {
+        if let Some(value) = self.lookup(&key) {
+            return value;
+        }
+        let mut stack = vec![key];
+        let mut result = None;
+        while let Some(top) = stack.last() {
+            let mut lookup = Lookup {
+                memo: self,
+                missing: None,
+            };
+            match (f(top, &mut lookup), lookup.missing) {
+                (Some(value), _) => {
+                    let top = stack.pop().unwrap();
+                    self.stats.misses += 1;
+                    self.store.insert(top, value.clone());
+                    result = Some(value);
+                }
+                (None, Some(missing)) => stack.push(missing),
+                (None, None) => panic!("Memoised function gave up without anything missing"),
+            }
+        }
+        result.unwrap()
+    }
+}
+
+/// How a function being run by [`Memo::evaluate`] gets at the values it depends on.
+pub struct Lookup<'a, K, V, S> {
+    memo: &'a mut Memo<K, V, S>,
+    missing: Option<K>,
+}
+
+impl<K: Clone, V: Clone, S: Store<K, V>> Lookup<'_, K, V, S> {
+    /// The value for `key` if it's known yet, so the caller can use `?` to give up if not.
+    pub fn get(&mut self, key: K) -> Option<V> {
+        let value = self.memo.lookup(&key);
+        if value.is_none() {
+            self.missing = Some(key);
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rayon::prelude::*;
+
+    fn fibonacci(memo: &mut Memo<u64, u64, impl Store<u64, u64>>, n: u64) -> u64 {
+        memo.call(n, &|memo, &n| {
+            if n < 2 {
+                n
+            } else {
+                fibonacci(memo, n - 1) + fibonacci(memo, n - 2)
+            }
+        })
+    }
+
+    #[test]
+    fn test_call() {
+        let mut memo = Memo::new(FxHashMap::default());
+        assert_eq!(fibonacci(&mut memo, 90), 2880067194370816120);
+        assert_eq!(
+            memo.stats(),
+            Stats {
+                hits: 88,
+                misses: 91
+            }
+        );
+        let mut memo = Memo::new(DenseStore::new(91, |&n: &u64| n as usize));
+        assert_eq!(fibonacci(&mut memo, 90), 2880067194370816120);
+        assert_eq!(memo.stats().misses, 91);
+    }
+
+    #[test]
+    fn test_evaluate_deep() {
+        const N: u64 = 1_000_000;
+        let mut memo = Memo::new(DenseStore::new(N as usize + 1, |&n: &u64| n as usize));
+        let triangle = |&n: &u64, lookup: &mut Lookup<'_, u64, u64, _>| {
+            Some(if n == 0 { 0 } else { lookup.get(n - 1)? + n })
+        };
+        assert_eq!(memo.evaluate(N, triangle), N * (N + 1) / 2);
+        // Every value but the first is worked out twice: once to find what it needs, then again
+        // once it's there.
+        assert_eq!(
+            memo.stats(),
+            Stats {
+                hits: N,
+                misses: N + 1
+            }
+        );
+        assert_eq!(memo.evaluate(10, triangle), 55);
+        assert_eq!(memo.stats().hits, N + 1);
+    }
+
+    #[test]
+    fn test_shared_store() {
+        let shared = DashMap::with_hasher(rustc_hash::FxBuildHasher);
+        let values: Vec<u64> = (0..64_u64)
+            .into_par_iter()
+            .map(|n| fibonacci(&mut Memo::new(&shared), n))
+            .collect();
+        assert_eq!(values[63], 6557470319842);
+        assert_eq!(shared.len(), 64);
+
+        let shared = RwLock::new(FxHashMap::default());
+        let values: Vec<u64> = (0..64_u64)
+            .into_par_iter()
+            .map(|n| fibonacci(&mut Memo::new(&shared), n))
+            .collect();
+        assert_eq!(values[63], 6557470319842);
+        assert_eq!(shared.read().len(), 64);
+    }
+}
@@ -0,0 +1,529 @@
+//! A small CDCL SAT solver (two watched literals, first-UIP learning, activity-based branching
+//! with phase saving, Luby restarts) plus constant-folding gate builders for bit-blasting.
+
+use rustc_hash::FxHashMap;
+use std::{cmp::Ordering, collections::BinaryHeap, ops::Not};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Lit(u32);
+
+impl Lit {
+    fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    fn code(self) -> usize {
+        self.0 as usize
+    }
+
+    fn is_negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+/// A bit in a bit-blasted circuit, which is either known up front or stands for a solver literal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bit {
+    Const(bool),
+    Lit(Lit),
+}
+
+impl Not for Bit {
+    type Output = Bit;
+
+    fn not(self) -> Bit {
+        match self {
+            Bit::Const(b) => Bit::Const(!b),
+            Bit::Lit(l) => Bit::Lit(!l),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Gate {
+    And,
+    Xor,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HeapEntry {
+    activity: f64,
+    var: usize,
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.activity
+            .total_cmp(&other.activity)
+            .then(other.var.cmp(&self.var))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    watches: Vec<Vec<usize>>,
+    assignment: Vec<Option<bool>>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    activity: Vec<f64>,
+    phase: Vec<bool>,
+    seen: Vec<bool>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    propagated: usize,
+    heap: BinaryHeap<HeapEntry>,
+    bump: f64,
+    unsat: bool,
+    gates: FxHashMap<(Gate, Lit, Lit), Lit>,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self {
+            bump: 1.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn new_var(&mut self) -> Lit {
+        let var = self.assignment.len();
+        self.assignment.push(None);
+        self.level.push(0);
+        self.reason.push(None);
+        self.activity.push(0.0);
+        self.phase.push(false);
+        self.seen.push(false);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.heap.push(HeapEntry { activity: 0.0, var });
+        Lit(var as u32 * 2)
+    }
+
+    pub fn fresh(&mut self) -> Bit {
+        Bit::Lit(self.new_var())
+    }
+
+    fn lit_value(assignment: &[Option<bool>], lit: Lit) -> Option<bool> {
+        assignment[lit.var()].map(|value| value != lit.is_negated())
+    }
+
+    /// The literal's value in the model found by the last successful [`Solver::solve`].
+    pub fn value(&self, lit: Lit) -> bool {
+        Self::lit_value(&self.assignment, lit).unwrap_or(false)
+    }
+
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        self.backtrack(0);
+        let mut clause: Vec<Lit> = Vec::with_capacity(lits.len());
+        for &lit in lits {
+            match Self::lit_value(&self.assignment, lit) {
+                Some(true) => return,
+                Some(false) => {}
+                None if clause.contains(&!lit) => return,
+                None if !clause.contains(&lit) => clause.push(lit),
+                None => {}
+            }
+        }
+        match clause.len() {
+            0 => self.unsat = true,
+            1 => self.enqueue(clause[0], None),
+            _ => {
+                let idx = self.clauses.len();
+                self.watches[clause[0].code()].push(idx);
+                self.watches[clause[1].code()].push(idx);
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    /// Adds a clause made of circuit bits, dropping constant-false bits.
+    pub fn add_bit_clause(&mut self, bits: &[Bit]) {
+        let mut lits = Vec::with_capacity(bits.len());
+        for &bit in bits {
+            match bit {
+                Bit::Const(true) => return,
+                Bit::Const(false) => {}
+                Bit::Lit(lit) => lits.push(lit),
+            }
+        }
+        self.add_clause(&lits);
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.assignment[var] = Some(!lit.is_negated());
+        self.level[var] = self.decision_level();
+        self.reason[var] = reason;
+        self.trail.push(lit);
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        let keep = self.trail_lim[level];
+        for lit in self.trail.drain(keep..) {
+            let var = lit.var();
+            self.phase[var] = !lit.is_negated();
+            self.assignment[var] = None;
+            self.reason[var] = None;
+            self.heap.push(HeapEntry {
+                activity: self.activity[var],
+                var,
+            });
+        }
+        self.trail_lim.truncate(level);
+        self.propagated = self.propagated.min(self.trail.len());
+    }
+
+    /// Unit propagation over the watched literals, returning a conflicting clause if one is found.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_lit = !self.trail[self.propagated];
+            self.propagated += 1;
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.code()]);
+            let mut i = 0;
+            while i < watchers.len() {
+                let idx = watchers[i];
+                let clause = &mut self.clauses[idx];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                if Self::lit_value(&self.assignment, clause[0]) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                let replacement = (2..clause.len())
+                    .find(|&k| Self::lit_value(&self.assignment, clause[k]) != Some(false));
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    self.watches[clause[1].code()].push(idx);
+                    watchers.swap_remove(i);
+                    continue;
+                }
+                let first = clause[0];
+                if Self::lit_value(&self.assignment, first) == Some(false) {
+                    self.watches[false_lit.code()] = watchers;
+                    return Some(idx);
+                }
+                self.enqueue(first, Some(idx));
+                i += 1;
+            }
+            self.watches[false_lit.code()] = watchers;
+        }
+        None
+    }
+
+    fn bump_var(&mut self, var: usize) {
+        self.activity[var] += self.bump;
+        if self.activity[var] > 1e100 {
+            self.activity.iter_mut().for_each(|a| *a *= 1e-100);
+            self.bump *= 1e-100;
+            let heap = std::mem::take(&mut self.heap);
+            self.heap = heap
+                .into_iter()
+                .map(|entry| HeapEntry {
+                    activity: self.activity[entry.var],
+                    var: entry.var,
+                })
+                .collect();
+        }
+        if self.assignment[var].is_none() {
+            self.heap.push(HeapEntry {
+                activity: self.activity[var],
+                var,
+            });
+        }
+    }
+
+    /// First-UIP conflict analysis, returning the learnt clause (asserting literal first) and the
+    /// level to backtrack to.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut clause_idx = conflict;
+        let mut skip_first = false;
+        let mut trail_idx = self.trail.len();
+        loop {
+            let start = usize::from(skip_first);
+            for k in start..self.clauses[clause_idx].len() {
+                let lit = self.clauses[clause_idx][k];
+                let var = lit.var();
+                if !self.seen[var] && self.level[var] > 0 {
+                    self.seen[var] = true;
+                    self.bump_var(var);
+                    if self.level[var] == self.decision_level() {
+                        pending += 1;
+                    } else {
+                        learnt.push(lit);
+                    }
+                }
+            }
+            let lit = loop {
+                trail_idx -= 1;
+                if self.seen[self.trail[trail_idx].var()] {
+                    break self.trail[trail_idx];
+                }
+            };
+            self.seen[lit.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !lit;
+                break;
+            }
+            clause_idx = self.reason[lit.var()].expect("Implied literal without a reason");
+            skip_first = true;
+        }
+        for lit in &learnt[1..] {
+            self.seen[lit.var()] = false;
+        }
+        self.bump /= 0.95;
+
+        let mut backtrack_level = 0;
+        if learnt.len() > 1 {
+            let max_idx = (1..learnt.len())
+                .max_by_key(|&k| self.level[learnt[k].var()])
+                .unwrap();
+            learnt.swap(1, max_idx);
+            backtrack_level = self.level[learnt[1].var()];
+        }
+        (learnt, backtrack_level)
+    }
+
+    fn pick_branch(&mut self) -> Option<Lit> {
+        while let Some(entry) = self.heap.pop() {
+            if self.assignment[entry.var].is_none() && entry.activity == self.activity[entry.var] {
+                let lit = Lit(entry.var as u32 * 2);
+                return Some(if self.phase[entry.var] { lit } else { !lit });
+            }
+        }
+        None
+    }
+
+    /// Searches for a model in which every literal in `assumptions` holds.
+    pub fn solve(&mut self, assumptions: &[Lit]) -> bool {
+        if self.unsat {
+            return false;
+        }
+        self.backtrack(0);
+        if self.propagate().is_some() {
+            self.unsat = true;
+            return false;
+        }
+
+        let mut conflicts = 0_u64;
+        let mut restart = 1_u32;
+        let mut restart_limit = 100 * luby(restart);
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.decision_level() == 0 {
+                    self.unsat = true;
+                    return false;
+                }
+                conflicts += 1;
+                let (learnt, backtrack_level) = self.analyze(conflict);
+                self.backtrack(backtrack_level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let idx = self.clauses.len();
+                    self.watches[learnt[0].code()].push(idx);
+                    self.watches[learnt[1].code()].push(idx);
+                    let asserting = learnt[0];
+                    self.clauses.push(learnt);
+                    self.enqueue(asserting, Some(idx));
+                }
+                continue;
+            }
+
+            if conflicts >= restart_limit {
+                conflicts = 0;
+                restart += 1;
+                restart_limit = 100 * luby(restart);
+                self.backtrack(0);
+                continue;
+            }
+
+            let level = self.decision_level();
+            let next = if let Some(&assumption) = assumptions.get(level) {
+                match Self::lit_value(&self.assignment, assumption) {
+                    Some(true) => {
+                        self.trail_lim.push(self.trail.len());
+                        continue;
+                    }
+                    Some(false) => return false,
+                    None => assumption,
+                }
+            } else {
+                match self.pick_branch() {
+                    Some(lit) => lit,
+                    None => return true,
+                }
+            };
+            self.trail_lim.push(self.trail.len());
+            self.enqueue(next, None);
+        }
+    }
+
+    fn gate(&mut self, gate: Gate, a: Lit, b: Lit, define: impl FnOnce(&mut Self, Lit)) -> Bit {
+        let key = (gate, a.min(b), a.max(b));
+        if let Some(&out) = self.gates.get(&key) {
+            return Bit::Lit(out);
+        }
+        let out = self.new_var();
+        define(self, out);
+        self.gates.insert(key, out);
+        Bit::Lit(out)
+    }
+
+    pub fn and(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(false), _) | (_, Bit::Const(false)) => Bit::Const(false),
+            (Bit::Const(true), x) | (x, Bit::Const(true)) => x,
+            (Bit::Lit(x), Bit::Lit(y)) if x == y => a,
+            (Bit::Lit(x), Bit::Lit(y)) if x == !y => Bit::Const(false),
+            (Bit::Lit(x), Bit::Lit(y)) => self.gate(Gate::And, x, y, |solver, out| {
+                solver.add_clause(&[!out, x]);
+                solver.add_clause(&[!out, y]);
+                solver.add_clause(&[out, !x, !y]);
+            }),
+        }
+    }
+
+    pub fn or(&mut self, a: Bit, b: Bit) -> Bit {
+        !self.and(!a, !b)
+    }
+
+    pub fn xor(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(c), x) | (x, Bit::Const(c)) => {
+                if c {
+                    !x
+                } else {
+                    x
+                }
+            }
+            (Bit::Lit(x), Bit::Lit(y)) if x == y => Bit::Const(false),
+            (Bit::Lit(x), Bit::Lit(y)) if x == !y => Bit::Const(true),
+            (Bit::Lit(x), Bit::Lit(y)) => {
+                // Normalise both inputs to positive literals so x ^ y and !x ^ !y share a gate.
+                let flip = x.is_negated() != y.is_negated();
+                let (x, y) = (Lit(x.0 & !1), Lit(y.0 & !1));
+                let out = self.gate(Gate::Xor, x, y, |solver, out| {
+                    solver.add_clause(&[!out, x, y]);
+                    solver.add_clause(&[!out, !x, !y]);
+                    solver.add_clause(&[out, !x, y]);
+                    solver.add_clause(&[out, x, !y]);
+                });
+                if flip {
+                    !out
+                } else {
+                    out
+                }
+            }
+        }
+    }
+
+    /// `select ? if_true : if_false`
+    pub fn mux(&mut self, select: Bit, if_true: Bit, if_false: Bit) -> Bit {
+        match select {
+            Bit::Const(true) => if_true,
+            Bit::Const(false) => if_false,
+            _ if if_true == if_false => if_true,
+            _ => {
+                let a = self.and(select, if_true);
+                let b = self.and(!select, if_false);
+                self.or(a, b)
+            }
+        }
+    }
+}
+
+fn luby(mut i: u32) -> u64 {
+    // Finds the i-th (1-based) element of 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ...
+    loop {
+        let k = 32 - i.leading_zeros();
+        if i == (1 << k) - 1 {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_luby() {
+        let seq: Vec<u64> = (1..=15).map(luby).collect();
+        assert_eq!(seq, [1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn test_pigeonhole_is_unsat() {
+        // Five pigeons, four holes.
+        let mut solver = Solver::new();
+        let vars: Vec<Vec<Lit>> = (0..5)
+            .map(|_| (0..4).map(|_| solver.new_var()).collect())
+            .collect();
+        for pigeon in &vars {
+            solver.add_clause(pigeon);
+        }
+        for (a, first) in vars.iter().enumerate() {
+            for second in &vars[a + 1..] {
+                for (&x, &y) in first.iter().zip(second) {
+                    solver.add_clause(&[!x, !y]);
+                }
+            }
+        }
+        assert!(!solver.solve(&[]));
+    }
+
+    #[test]
+    fn test_assumptions_and_gates() {
+        let mut solver = Solver::new();
+        let x = solver.fresh();
+        let y = solver.fresh();
+        let z = solver.xor(x, y);
+        let both = solver.and(x, y);
+        assert_eq!(solver.xor(x, x), Bit::Const(false));
+        assert_eq!(solver.xor(!x, !y), z);
+        solver.add_bit_clause(&[z]);
+        let (Bit::Lit(xl), Bit::Lit(yl), Bit::Lit(bl)) = (x, y, both) else {
+            unreachable!()
+        };
+        assert!(solver.solve(&[xl]));
+        assert!(solver.value(xl) && !solver.value(yl));
+        assert!(!solver.solve(&[bl]));
+        assert!(solver.solve(&[!xl]));
+        assert!(solver.value(yl));
+    }
+}
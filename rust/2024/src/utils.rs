@@ -1,4 +1,5 @@
 use anyhow::{Error, Result};
+pub use aoc_common::input::{check_line_shape, normalize_input, validate_lines};
 pub use aoc_runner_derive::{aoc, aoc_generator};
 use nom::{
     character::complete::space0,
@@ -76,3 +77,16 @@ pub fn arr_or<const N: usize>(a: [bool; N], b: [bool; N]) -> [bool; N] {
     }
     out
 }
+
+/// A xorshift generator for randomised tests: each call returns a value below its argument, and the
+/// same `seed` always gives the same sequence. `seed` must not be zero.
+#[cfg(test)]
+pub fn xorshift(seed: u64) -> impl FnMut(u64) -> u64 {
+    let mut state = seed;
+    move |n| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % n
+    }
+}
@@ -1,87 +1,53 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use aoc_2025::run_day;
-use fnv::FnvHashMap;
+use fnv::FnvBuildHasher;
 
 type NodeId = [u8; 3];
-type Graph = FnvHashMap<NodeId, Vec<NodeId>>;
+type Graph = aoc_common::graph::Graph<NodeId, FnvBuildHasher>;
 
-const END: NodeId = [b'o', b'u', b't'];
+const END: NodeId = *b"out";
 
 fn part1(s: &str) -> Result<u64> {
-    const START: NodeId = [b'y', b'o', b'u'];
+    const START: NodeId = *b"you";
     let graph = parse_graph(s);
-    let mut paths_to_out: FnvHashMap<NodeId, u64> =
-        FnvHashMap::with_capacity_and_hasher(graph.len(), Default::default());
-    paths_to_out.insert(END, 1);
-    Ok(recursive_path_search(&mut paths_to_out, &graph, START))
+    graph.count_paths(node(&graph, START)?, node(&graph, END)?)
 }
 
 fn parse_graph(s: &str) -> Graph {
-    s.lines()
-        .filter(|line| line.len() > 5)
-        .map(|line| {
-            let dsts = line.as_bytes()[5..]
-                .windows(3)
-                .step_by(4)
-                .map(|window| {
-                    window
-                        .try_into()
-                        .expect("Windows didn't give a length 3 slice")
-                })
-                .collect::<Vec<_>>();
-            (line.as_bytes()[..3].try_into().unwrap(), dsts)
-        })
-        .collect()
-}
-
-fn recursive_path_search(memo: &mut FnvHashMap<NodeId, u64>, graph: &Graph, start: NodeId) -> u64 {
-    if let Some(&count) = memo.get(&start) {
-        return count;
+    let mut graph = Graph::new();
+    for line in s.lines().filter(|line| line.len() > 5) {
+        let src: NodeId = line.as_bytes()[..3].try_into().unwrap();
+        for window in line.as_bytes()[5..].windows(3).step_by(4) {
+            let dst: NodeId = window
+                .try_into()
+                .expect("Windows didn't give a length 3 slice");
+            graph.add_edge(src, dst);
+        }
     }
+    graph
+}
 
-    let count = graph
-        .get(&start)
-        .map(|neighbours| {
-            neighbours
-                .iter()
-                .copied()
-                .map(|node| recursive_path_search(memo, graph, node))
-                .sum()
-        })
-        .unwrap_or_default();
-    memo.insert(start, count);
-    count
+fn node(graph: &Graph, label: NodeId) -> Result<usize> {
+    graph
+        .id(&label)
+        .with_context(|| format!("Missing node {}", String::from_utf8_lossy(&label)))
 }
 
 fn part2(s: &str) -> Result<u64> {
-    const SERVER: NodeId = [b's', b'v', b'r'];
-    const DAC: NodeId = [b'd', b'a', b'c'];
-    const FFT: NodeId = [b'f', b'f', b't'];
+    const SERVER: NodeId = *b"svr";
+    const DAC: NodeId = *b"dac";
+    const FFT: NodeId = *b"fft";
     let graph = parse_graph(s);
-    let mut memo_cache: FnvHashMap<NodeId, u64> =
-        FnvHashMap::with_capacity_and_hasher(graph.len(), Default::default());
-
-    memo_cache.insert(FFT, 1);
-    let (to_fft_coeff, dac_start, end_start) = {
-        let dac = recursive_path_search(&mut memo_cache, &graph, DAC);
-        // For the problem to have an answer, either dac -> fft or fft -> dac is unreachable.
-        // Will split into cases based on that.
-        if dac != 0 {
-            (dac, SERVER, FFT)
-        } else {
-            let svr = recursive_path_search(&mut memo_cache, &graph, SERVER);
-            (svr, FFT, DAC)
-        }
-    };
-    memo_cache.clear();
-
-    memo_cache.insert(DAC, 1);
-    let to_dac_coeff = recursive_path_search(&mut memo_cache, &graph, dac_start);
-    memo_cache.clear();
-    memo_cache.insert(END, 1);
-    let to_end_coeff = recursive_path_search(&mut memo_cache, &graph, end_start);
+    let server = node(&graph, SERVER)?;
+    let dac = node(&graph, DAC)?;
+    let fft = node(&graph, FFT)?;
+    let end = node(&graph, END)?;
 
-    Ok(to_end_coeff * to_dac_coeff * to_fft_coeff)
+    let to_dac = graph.path_counts_to(dac)?;
+    let to_fft = graph.path_counts_to(fft)?;
+    let to_end = graph.path_counts_to(end)?;
+    // The graph is acyclic, so at most one of dac -> fft and fft -> dac has any paths.
+    Ok(to_dac[server] * to_fft[dac] * to_end[fft] + to_fft[server] * to_dac[fft] * to_end[dac])
 }
 
 pub fn main() -> Result<()> {
@@ -1,40 +1,293 @@
-use anyhow::{Context, Result};
-use aoc_2025::run_day;
+use anyhow::{Context, Result, anyhow};
+use aoc_2025::{byte_grid::Grid, exact_cover::ExactCoverBuilder, run_day};
 
-fn part1(s: &str) -> Result<u64> {
+/// A present's cells as `(row, col)` offsets, sorted, with the top row and left column in use.
+type Shape = Vec<(usize, usize)>;
+
+fn normalise(mut cells: Vec<(usize, usize)>) -> Shape {
+    let min_row = cells.iter().map(|&(row, _)| row).min().unwrap_or(0);
+    let min_col = cells.iter().map(|&(_, col)| col).min().unwrap_or(0);
+    for (row, col) in &mut cells {
+        *row -= min_row;
+        *col -= min_col;
+    }
+    cells.sort_unstable();
+    cells
+}
+
+/// Every distinct rotation and reflection of `shape`.
+fn orientations(shape: &Shape) -> Vec<Shape> {
+    let size = shape
+        .iter()
+        .map(|&(row, col)| row.max(col) + 1)
+        .max()
+        .unwrap_or(0);
+    let mut result: Vec<Shape> = Vec::new();
+    let mut current = shape.clone();
+    for _ in 0..4 {
+        let reflected = current.iter().map(|&(row, col)| (row, size - 1 - col));
+        for candidate in [current.clone(), normalise(reflected.collect())] {
+            if !result.contains(&candidate) {
+                result.push(candidate);
+            }
+        }
+        current = normalise(
+            current
+                .iter()
+                .map(|&(row, col)| (col, size - 1 - row))
+                .collect(),
+        );
+    }
+    result
+}
+
+struct Region {
+    width: usize,
+    height: usize,
+    counts: Vec<usize>,
+}
+
+fn parse(s: &str) -> Result<(Vec<Shape>, Vec<Region>)> {
     let mut parts = s.trim().split("\n\n").collect::<Vec<_>>();
-    let regions = parts.pop().unwrap();
-    let boxes = parts
+    let regions = parts.pop().context("Empty input")?;
+    let shapes = parts
         .into_iter()
         .map(|part| {
-            part.as_bytes()
-                .iter()
-                .copied()
-                .filter(|&b| b == b'#')
-                .count() as u64
+            let (_, cells) = part.split_once('\n').context("Shape has no cells")?;
+            let grid = Grid::from_input_str(cells)?;
+            Ok(normalise(
+                grid.enumerate()
+                    .filter(|&(_, &b)| b == b'#')
+                    .map(|(pos, _)| pos)
+                    .collect(),
+            ))
         })
-        .collect::<Vec<_>>();
-
-    Ok(regions
+        .collect::<Result<Vec<_>>>()?;
+    let regions = regions
         .lines()
-        .filter(|&line| {
-            let (dimensions, counts) = line.split_once(": ").unwrap();
-            let (x, y) = dimensions.split_once("x").unwrap();
-            let area = x.parse::<u64>().unwrap() * y.parse::<u64>().unwrap();
-            area >= counts
+        .map(|line| {
+            let (dimensions, counts) = line
+                .split_once(": ")
+                .ok_or_else(|| anyhow!("Bad region {line:?}"))?;
+            let (width, height) = dimensions
+                .split_once('x')
+                .ok_or_else(|| anyhow!("Bad dimensions {dimensions:?}"))?;
+            let counts = counts
                 .split_whitespace()
-                .map(|count| count.parse::<u64>().unwrap())
-                .zip(boxes.iter().copied())
-                .map(|(count, size)| count * size)
-                .sum()
+                .map(|count| Ok(count.parse::<usize>()?))
+                .collect::<Result<Vec<_>>>()?;
+            if counts.len() > shapes.len() {
+                return Err(anyhow!("Region {line:?} wants more shapes than there are"));
+            }
+            Ok(Region {
+                width: width.parse()?,
+                height: height.parse()?,
+                counts,
+            })
         })
-        .count() as u64)
+        .collect::<Result<Vec<_>>>()?;
+    Ok((shapes, regions))
 }
 
-fn part2(s: &str) -> Result<&'static str> {
-    todo!()
+/// How many rows the exact cover search may choose for one region before giving up.
+const SEARCH_STEPS: u64 = 10_000_000;
+
+fn fits(shapes: &[Shape], region: &Region, step_limit: u64) -> Result<bool> {
+    let area = region.width * region.height;
+    let needed: usize = region
+        .counts
+        .iter()
+        .zip(shapes)
+        .map(|(count, shape)| count * shape.len())
+        .sum();
+    if needed > area {
+        return Ok(false);
+    }
+    // If every present gets a bounding box to itself, there's nothing to search.
+    let side = shapes
+        .iter()
+        .flatten()
+        .map(|&(row, col)| row.max(col) + 1)
+        .max()
+        .unwrap_or(1);
+    let presents: usize = region.counts.iter().sum();
+    if (region.width / side) * (region.height / side) >= presents {
+        return Ok(true);
+    }
+
+    let kinds: Vec<(usize, &Shape)> = region
+        .counts
+        .iter()
+        .copied()
+        .zip(shapes)
+        .filter(|&(count, _)| count > 0)
+        .collect();
+    let mut placements: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (kind, &(_, shape)) in kinds.iter().enumerate() {
+        let before = placements.len();
+        for orientation in orientations(shape) {
+            let height = orientation
+                .iter()
+                .map(|&(row, _)| row + 1)
+                .max()
+                .unwrap_or(0);
+            let width = orientation
+                .iter()
+                .map(|&(_, col)| col + 1)
+                .max()
+                .unwrap_or(0);
+            for top in 0..(region.height + 1).saturating_sub(height) {
+                for left in 0..(region.width + 1).saturating_sub(width) {
+                    let cells = orientation
+                        .iter()
+                        .map(|&(row, col)| (top + row) * region.width + left + col)
+                        .collect();
+                    placements.push((kind, cells));
+                }
+            }
+        }
+        if placements.len() == before {
+            return Ok(false);
+        }
+    }
+
+    // Cells no placement reaches have to be left blank, so only the rest get columns, and the
+    // presents need to fit in those.
+    let mut columns = vec![usize::MAX; area];
+    let mut coverable = 0;
+    for &cell in placements.iter().flat_map(|(_, cells)| cells) {
+        if columns[cell] == usize::MAX {
+            columns[cell] = kinds.len() + coverable;
+            coverable += 1;
+        }
+    }
+    if coverable < needed {
+        return Ok(false);
+    }
+
+    // A column for each kind of present, one for each coverable cell, and one for the cells left
+    // blank.
+    let blank = kinds.len() + coverable;
+    let blanks = coverable - needed;
+    let mut builder = ExactCoverBuilder::new(blank + usize::from(blanks > 0), 0);
+    builder.step_limit(step_limit);
+    for (kind, &(count, _)) in kinds.iter().enumerate() {
+        builder.need(kind, count);
+    }
+    if blanks > 0 {
+        builder.need(blank, blanks);
+    }
+    for (kind, cells) in &placements {
+        let mut row = vec![*kind];
+        row.extend(cells.iter().map(|&cell| columns[cell]));
+        builder.row(&row);
+    }
+    if blanks > 0 {
+        for column in kinds.len()..blank {
+            builder.row(&[blank, column]);
+        }
+    }
+    let solution = builder.build()?.first().with_context(|| {
+        format!(
+            "Couldn't decide whether the {}x{} region fits",
+            region.width, region.height
+        )
+    })?;
+    Ok(solution.is_some())
+}
+
+fn part1(s: &str) -> Result<u64> {
+    let (shapes, regions) = parse(s)?;
+    let mut count = 0;
+    for region in &regions {
+        if fits(&shapes, region, SEARCH_STEPS)? {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// The last day only has the one puzzle.
+fn part2(_: &str) -> Result<&'static str> {
+    Ok("Merry Christmas!")
 }
 
 pub fn main() -> Result<()> {
     run_day(part1, part2)
 }
+
+#[cfg(test)]
+static TEST_INPUT: &str = "0:
+###
+##.
+##.
+
+1:
+###
+##.
+.##
+
+2:
+.##
+###
+##.
+
+3:
+##.
+###
+##.
+
+4:
+###
+#..
+###
+
+5:
+###
+.#.
+###
+
+4x4: 0 0 0 0 2 0
+12x5: 1 0 1 0 2 2
+12x5: 1 0 1 0 3 2
+";
+
+#[test]
+fn test_part1() {
+    assert_eq!(part1(TEST_INPUT).unwrap(), 2);
+}
+
+#[test]
+fn test_orientations() {
+    let (shapes, _) = parse(TEST_INPUT).unwrap();
+    assert_eq!(orientations(&shapes[0]).len(), 8);
+    assert_eq!(orientations(&shapes[5]).len(), 2);
+    assert_eq!(orientations(&vec![(0, 0), (0, 1), (1, 0), (1, 1)]).len(), 1);
+}
+
+#[test]
+fn test_fits() {
+    let (shapes, regions) = parse(TEST_INPUT).unwrap();
+    let fit = |region| fits(&shapes, region, SEARCH_STEPS).unwrap();
+    assert!(fit(&regions[0]));
+    assert!(fit(&regions[1]));
+    // 49 cells of presents in a 60 cell region, but they can't be packed in.
+    assert!(!fit(&regions[2]));
+    assert!(fits(&shapes, &regions[2], 10).is_err());
+    // Nothing 3 wide fits in 2 rows, however much room there is.
+    let narrow = Region {
+        width: 20,
+        height: 2,
+        counts: vec![0, 0, 0, 1],
+    };
+    assert!(!fit(&narrow));
+    // Two pluses fit by area in 4x3, but the corners are out of reach, leaving only 8 cells. That
+    // shows up before any searching.
+    let plus = normalise(vec![(0, 1), (1, 0), (1, 1), (1, 2), (2, 1)]);
+    let corners = Region {
+        width: 4,
+        height: 3,
+        counts: vec![2],
+    };
+    assert!(!fits(&[plus], &corners, 0).unwrap());
+}
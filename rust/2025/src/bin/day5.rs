@@ -1,42 +1,28 @@
 use anyhow::Result;
+use aoc_2025::interval_set::IntervalSet;
 use aoc_2025::run_day;
-use itertools::Itertools;
 
 fn part1(s: &str) -> Result<u64> {
     let mut lines = s.trim().lines();
-    let ranges: Vec<_> = make_ranges(lines.by_ref().take_while(|l| !l.is_empty())).collect();
+    let fresh = make_ranges(lines.by_ref().take_while(|l| !l.is_empty()));
     Ok(lines
         .map(|l| l.parse::<u64>().unwrap())
-        .filter(|id| match ranges.binary_search_by_key(id, |(a, _)| *a) {
-            Ok(idx) => ranges[idx].0 <= *id && *id <= ranges[idx].1,
-            Err(0) => false,
-            Err(idx) => ranges[idx - 1].0 <= *id && *id <= ranges[idx - 1].1,
-        })
+        .filter(|&id| fresh.contains(id))
         .count() as u64)
 }
 
-// Also sorted by increasing start
-fn make_ranges<'a>(lines: impl IntoIterator<Item = &'a str>) -> impl Iterator<Item = (u64, u64)> {
+fn make_ranges<'a>(lines: impl IntoIterator<Item = &'a str>) -> IntervalSet<u64> {
     lines
         .into_iter()
         .map(|line| {
             let (a, b) = line.split_once('-').unwrap();
-            (a.parse::<u64>().unwrap(), b.parse::<u64>().unwrap())
-        })
-        .sorted_unstable()
-        .coalesce(|(start, end), (next_start, next_end)| {
-            if start <= next_start && next_start <= end {
-                Ok((start, end.max(next_end)))
-            } else {
-                Err(((start, end), (next_start, next_end)))
-            }
+            a.parse::<u64>().unwrap()..b.parse::<u64>().unwrap() + 1
         })
+        .collect()
 }
 
 fn part2(s: &str) -> Result<u64> {
-    Ok(make_ranges(s.trim().lines().take_while(|l| !l.is_empty()))
-        .map(|(a, b)| b - a + 1)
-        .sum())
+    Ok(make_ranges(s.trim().lines().take_while(|l| !l.is_empty())).total_len())
 }
 
 pub fn main() -> Result<()> {
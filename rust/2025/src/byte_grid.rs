@@ -15,17 +15,26 @@ impl Grid<u8> {
         Grid::from_input_lines(s.lines())
     }
 
+    /// Builds a grid from `it`, ignoring a leading byte-order mark, a `\r` left over from CRLF
+    /// line endings and any blank lines at the end of the input.
     pub fn from_input_lines<'a>(it: impl IntoIterator<Item = &'a str>) -> Result<Self> {
-        let mut lines = it.into_iter().peekable();
-        let width = lines.peek().map_or(0, |l| l.len());
-        let mut height = 0;
-        let mut data: Vec<u8> =
-            Vec::with_capacity(lines.size_hint().1.map_or(width, |x| x * width));
+        let mut lines: Vec<&str> = it
+            .into_iter()
+            .map(|l| l.strip_suffix('\r').unwrap_or(l))
+            .collect();
+        if let Some(first) = lines.first_mut() {
+            *first = first.strip_prefix('\u{feff}').unwrap_or(first);
+        }
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        let width = lines.first().map_or(0, |l| l.len());
+        let height = lines.len();
+        let mut data: Vec<u8> = Vec::with_capacity(width * height);
         for line in lines {
             if line.len() != width {
                 return Err(anyhow!("Grid is ragged"));
             }
-            height += 1;
             data.extend_from_slice(line.as_bytes());
         }
         Ok(Grid {
@@ -34,6 +43,7 @@ impl Grid<u8> {
             width,
         })
     }
+
     pub fn find(&self, val: u8) -> Option<(usize, usize)> {
         memchr(val, self.data.as_slice()).map(|idx| (idx / self.width, idx % self.width))
     }
@@ -152,3 +162,16 @@ impl<T> IndexMut<(usize, usize)> for Grid<T> {
         self.get_mut(row, col).unwrap()
     }
 }
+
+#[test]
+fn test_from_windows_input() {
+    let unix = Grid::from_input_str("#.@\n@@.\n").unwrap();
+    let windows = Grid::from_input_str("\u{feff}#.@\r\n@@.\r\n\r\n").unwrap();
+    assert_eq!(unix, windows);
+    assert_eq!((windows.width(), windows.height()), (3, 2));
+    assert_eq!(
+        Grid::from_input_lines("#.@\r\n@@.\r\n".split('\n')).unwrap(),
+        unix
+    );
+    assert!(Grid::from_input_lines("#.@\n@@\n".split('\n')).is_err());
+}
@@ -0,0 +1,459 @@
+use std::ops::ControlFlow;
+
+use anyhow::{Result, anyhow};
+
+/// Describes an exact cover problem: rows are lists of column indices, primary columns
+/// `0..primary` must each be covered by exactly as many chosen rows as they need (once, unless
+/// raised with [`need`](Self::need)), and secondary columns `primary..primary + secondary` may be
+/// covered at most once.
+#[derive(Clone, Debug)]
+pub struct ExactCoverBuilder {
+    needs: Vec<usize>,
+    secondary: usize,
+    rows: Vec<Vec<usize>>,
+    step_limit: Option<u64>,
+}
+
+impl ExactCoverBuilder {
+    pub fn new(primary: usize, secondary: usize) -> Self {
+        ExactCoverBuilder {
+            needs: vec![1; primary],
+            secondary,
+            rows: Vec::new(),
+            step_limit: None,
+        }
+    }
+
+    /// Requires primary `column` to be covered by exactly `times` rows.
+    pub fn need(&mut self, column: usize, times: usize) -> &mut Self {
+        self.needs[column] = times;
+        self
+    }
+
+    /// Adds a row and returns its index, which is how solutions refer to it.
+    pub fn row(&mut self, columns: &[usize]) -> usize {
+        self.rows.push(columns.to_vec());
+        self.rows.len() - 1
+    }
+
+    /// Makes searches give up with an error once they've tried choosing this many rows.
+    pub fn step_limit(&mut self, limit: u64) -> &mut Self {
+        self.step_limit = Some(limit);
+        self
+    }
+
+    pub fn build(&self) -> Result<ExactCover> {
+        let columns = self.needs.len() + self.secondary;
+        if let Some(column) = self.needs.iter().position(|&need| need == 0) {
+            return Err(anyhow!(
+                "Primary column {column} needs to be covered at least once"
+            ));
+        }
+        let mut solver = ExactCover::new(&self.needs, self.secondary, self.step_limit);
+        let mut seen = vec![usize::MAX; columns];
+        for (index, row) in self.rows.iter().enumerate() {
+            if row.is_empty() {
+                return Err(anyhow!("Row {index} covers no columns"));
+            }
+            for &column in row {
+                if column >= columns {
+                    return Err(anyhow!("Row {index} covers column {column} of {columns}"));
+                }
+                if seen[column] == index {
+                    return Err(anyhow!("Row {index} covers column {column} twice"));
+                }
+                seen[column] = index;
+            }
+            solver.add_row(index, row);
+        }
+        Ok(solver)
+    }
+}
+
+/// Knuth's Algorithm X over dancing links, built with [`ExactCoverBuilder`].
+///
+/// When branching on a column that needs several rows, they're chosen in the order they were
+/// added, so each set of rows is found once rather than once per permutation and counts are exact.
+#[derive(Clone, Debug)]
+pub struct ExactCover {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    row: Vec<usize>,
+    size: Vec<usize>,
+    need: Vec<usize>,
+    covered: Vec<bool>,
+    step_limit: Option<u64>,
+    steps: u64,
+    chosen: Vec<usize>,
+}
+
+impl ExactCover {
+    /// Node 0 is the root and the next nodes are the column headers. Only primary headers are
+    /// linked into the root's list, so secondary columns are never branched on.
+    fn new(needs: &[usize], secondary: usize, step_limit: Option<u64>) -> Self {
+        let primary = needs.len() + 1;
+        let headers = primary + secondary;
+        let mut left: Vec<usize> = (0..headers).collect();
+        let mut right: Vec<usize> = (0..headers).collect();
+        for header in 0..primary {
+            left[header] = (header + primary - 1) % primary;
+            right[header] = (header + 1) % primary;
+        }
+        ExactCover {
+            left,
+            right,
+            up: (0..headers).collect(),
+            down: (0..headers).collect(),
+            column: (0..headers).collect(),
+            row: vec![usize::MAX; headers],
+            size: vec![0; headers],
+            need: std::iter::once(0)
+                .chain(needs.iter().copied())
+                .chain(std::iter::repeat_n(1, secondary))
+                .collect(),
+            covered: vec![false; headers],
+            step_limit,
+            steps: 0,
+            chosen: Vec::new(),
+        }
+    }
+
+    fn add_row(&mut self, index: usize, columns: &[usize]) {
+        let first = self.left.len();
+        for &column in columns {
+            let header = column + 1;
+            let node = self.left.len();
+            self.column.push(header);
+            self.row.push(index);
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            let above = self.up[header];
+            self.down[above] = node;
+            self.up[header] = node;
+            self.size[header] += 1;
+            self.left.push(if node == first { node } else { node - 1 });
+            self.right.push(first);
+            let prev = self.left[node];
+            self.right[prev] = node;
+            self.left[first] = node;
+        }
+    }
+
+    fn unlink_vertically(&mut self, node: usize) {
+        let (u, d) = (self.up[node], self.down[node]);
+        self.down[u] = d;
+        self.up[d] = u;
+        self.size[self.column[node]] -= 1;
+    }
+
+    fn relink_vertically(&mut self, node: usize) {
+        let (u, d) = (self.up[node], self.down[node]);
+        self.down[u] = node;
+        self.up[d] = node;
+        self.size[self.column[node]] += 1;
+    }
+
+    fn cover(&mut self, header: usize) {
+        self.covered[header] = true;
+        let (l, r) = (self.left[header], self.right[header]);
+        self.right[l] = r;
+        self.left[r] = l;
+        let mut row = self.down[header];
+        while row != header {
+            let mut node = self.right[row];
+            while node != row {
+                self.unlink_vertically(node);
+                node = self.right[node];
+            }
+            row = self.down[row];
+        }
+    }
+
+    fn uncover(&mut self, header: usize) {
+        let mut row = self.up[header];
+        while row != header {
+            let mut node = self.left[row];
+            while node != row {
+                self.relink_vertically(node);
+                node = self.left[node];
+            }
+            row = self.up[row];
+        }
+        let (l, r) = (self.left[header], self.right[header]);
+        self.right[l] = header;
+        self.left[r] = header;
+        self.covered[header] = false;
+    }
+
+    /// Counts `row` towards each of its columns besides the one it was chosen from, covering those
+    /// it completes.
+    fn choose(&mut self, row: usize) {
+        self.chosen.push(self.row[row]);
+        let mut node = self.right[row];
+        while node != row {
+            let header = self.column[node];
+            if self.need[header] > 1 {
+                self.need[header] -= 1;
+            } else {
+                self.cover(header);
+            }
+            node = self.right[node];
+        }
+    }
+
+    fn unchoose(&mut self, row: usize) {
+        let mut node = self.left[row];
+        while node != row {
+            let header = self.column[node];
+            if self.covered[header] {
+                self.uncover(header);
+            } else {
+                self.need[header] += 1;
+            }
+            node = self.left[node];
+        }
+        self.chosen.pop();
+    }
+
+    /// Takes `row` out of every column it's in, without covering anything.
+    fn hide(&mut self, row: usize) {
+        let mut node = row;
+        loop {
+            self.unlink_vertically(node);
+            node = self.right[node];
+            if node == row {
+                break;
+            }
+        }
+    }
+
+    fn unhide(&mut self, row: usize) {
+        let mut node = self.left[row];
+        loop {
+            self.relink_vertically(node);
+            if node == row {
+                break;
+            }
+            node = self.left[node];
+        }
+    }
+
+    fn step(&mut self) -> Result<()> {
+        self.steps += 1;
+        match self.step_limit {
+            Some(limit) if self.steps > limit => {
+                Err(anyhow!("Exact cover search gave up after {limit} steps"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn search(
+        &mut self,
+        found: &mut dyn FnMut(&[usize]) -> ControlFlow<()>,
+    ) -> Result<ControlFlow<()>> {
+        if self.right[0] == 0 {
+            return Ok(found(&self.chosen));
+        }
+        // Branch on the column with the fewest spare rows.
+        let mut best = self.right[0];
+        let mut header = best;
+        while header != 0 {
+            if self.size[header] < self.need[header] {
+                return Ok(ControlFlow::Continue(()));
+            }
+            if self.size[header] - self.need[header] < self.size[best] - self.need[best] {
+                best = header;
+            }
+            header = self.right[header];
+        }
+
+        let mut flow = ControlFlow::Continue(());
+        let mut result = Ok(());
+        if self.need[best] == 1 {
+            self.cover(best);
+            let mut row = self.down[best];
+            while row != best && flow.is_continue() {
+                if let Err(e) = self.step() {
+                    result = Err(e);
+                    break;
+                }
+                self.choose(row);
+                let outcome = self.search(found);
+                self.unchoose(row);
+                match outcome {
+                    Ok(outcome) => flow = outcome,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+                row = self.down[row];
+            }
+            self.uncover(best);
+        } else {
+            // Each row is hidden once tried, so later choices in this column can't reuse it or
+            // pick the same rows in a different order.
+            let mut hidden = Vec::new();
+            let mut row = self.down[best];
+            while row != best && flow.is_continue() {
+                if let Err(e) = self.step() {
+                    result = Err(e);
+                    break;
+                }
+                self.hide(row);
+                hidden.push(row);
+                self.need[best] -= 1;
+                self.choose(row);
+                let outcome = self.search(found);
+                self.unchoose(row);
+                self.need[best] += 1;
+                match outcome {
+                    Ok(outcome) => flow = outcome,
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+                row = self.down[row];
+            }
+            for row in hidden.into_iter().rev() {
+                self.unhide(row);
+            }
+        }
+        // The links are restored even when giving up, so the solver can be searched again.
+        result?;
+        Ok(flow)
+    }
+
+    /// Calls `found` with the rows of each solution, in the order they were chosen, until it
+    /// breaks or the solutions run out.
+    pub fn for_each_solution(
+        &mut self,
+        mut found: impl FnMut(&[usize]) -> ControlFlow<()>,
+    ) -> Result<()> {
+        self.steps = 0;
+        self.search(&mut found).map(|_| ())
+    }
+
+    /// The rows of some solution, sorted, or `None` if there isn't one.
+    pub fn first(&mut self) -> Result<Option<Vec<usize>>> {
+        let mut solution = None;
+        self.for_each_solution(|rows| {
+            let mut rows = rows.to_vec();
+            rows.sort_unstable();
+            solution = Some(rows);
+            ControlFlow::Break(())
+        })?;
+        Ok(solution)
+    }
+
+    /// The rows of every solution, each sorted.
+    pub fn all(&mut self) -> Result<Vec<Vec<usize>>> {
+        let mut solutions = Vec::new();
+        self.for_each_solution(|rows| {
+            let mut rows = rows.to_vec();
+            rows.sort_unstable();
+            solutions.push(rows);
+            ControlFlow::Continue(())
+        })?;
+        Ok(solutions)
+    }
+
+    pub fn count(&mut self) -> Result<u64> {
+        let mut count = 0;
+        self.for_each_solution(|_| {
+            count += 1;
+            ControlFlow::Continue(())
+        })?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+fn queens(n: usize) -> ExactCoverBuilder {
+    // Ranks and files are primary, the diagonals secondary.
+    let diagonals = 2 * n - 1;
+    let mut builder = ExactCoverBuilder::new(2 * n, 2 * diagonals);
+    for rank in 0..n {
+        for file in 0..n {
+            builder.row(&[
+                rank,
+                n + file,
+                2 * n + rank + file,
+                2 * n + diagonals + rank + n - 1 - file,
+            ]);
+        }
+    }
+    builder
+}
+
+#[test]
+fn test_knuth_example() {
+    let mut builder = ExactCoverBuilder::new(7, 0);
+    for row in [
+        &[2, 4, 5][..],
+        &[0, 3, 6],
+        &[1, 2, 5],
+        &[0, 3],
+        &[1, 6],
+        &[3, 4, 6],
+    ] {
+        builder.row(row);
+    }
+    let mut solver = builder.build().unwrap();
+    assert_eq!(solver.first().unwrap(), Some(vec![0, 3, 4]));
+    assert_eq!(solver.all().unwrap(), vec![vec![0, 3, 4]]);
+    assert_eq!(solver.count().unwrap(), 1);
+}
+
+#[test]
+fn test_secondary_columns() {
+    assert_eq!(queens(6).build().unwrap().count().unwrap(), 4);
+    assert_eq!(queens(8).build().unwrap().count().unwrap(), 92);
+    let mut solver = queens(3).build().unwrap();
+    assert_eq!(solver.first().unwrap(), None);
+}
+
+#[test]
+fn test_multiplicities() {
+    // Two of the three rows covering column 0, plus one of the two covering column 1.
+    let mut builder = ExactCoverBuilder::new(2, 0);
+    builder.need(0, 2);
+    for row in [&[0][..], &[0], &[0], &[1], &[1]] {
+        builder.row(row);
+    }
+    assert_eq!(builder.build().unwrap().count().unwrap(), 6);
+
+    // A row counting towards a column that needs more isn't reused to make up the numbers.
+    let mut builder = ExactCoverBuilder::new(2, 0);
+    builder.need(0, 2).need(1, 2);
+    builder.row(&[0, 1]);
+    builder.row(&[0]);
+    builder.row(&[1]);
+    let mut solver = builder.build().unwrap();
+    assert_eq!(solver.all().unwrap(), vec![vec![0, 1, 2]]);
+}
+
+#[test]
+fn test_step_limit_and_validation() {
+    let mut builder = queens(8);
+    builder.step_limit(100);
+    let mut solver = builder.build().unwrap();
+    assert!(solver.count().is_err());
+    // A failed search leaves the solver usable, and the limit applies to each search afresh.
+    assert!(solver.first().unwrap().is_some());
+
+    let mut builder = ExactCoverBuilder::new(1, 0);
+    builder.row(&[1]);
+    assert!(builder.build().is_err());
+    let mut builder = ExactCoverBuilder::new(2, 0);
+    builder.row(&[0, 0]);
+    assert!(builder.build().is_err());
+    let mut builder = ExactCoverBuilder::new(2, 0);
+    builder.need(1, 0);
+    assert!(builder.build().is_err());
+}
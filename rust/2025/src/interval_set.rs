@@ -0,0 +1,293 @@
+use std::{
+    iter::Sum,
+    ops::{Add, Range, Sub},
+};
+
+use anyhow::{Result, anyhow};
+use itertools::Itertools;
+
+/// A set of values stored as sorted, disjoint, non-touching half-open ranges.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    ranges: Vec<Range<T>>,
+}
+
+impl<T> Default for IntervalSet<T> {
+    fn default() -> Self {
+        IntervalSet { ranges: Vec::new() }
+    }
+}
+
+impl<T: Copy + Ord> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ranges(&self) -> &[Range<T>] {
+        &self.ranges
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Range<T>> + '_ {
+        self.ranges.iter().cloned()
+    }
+
+    /// The number of disjoint ranges, not the number of values in the set.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Adds `range` to the set, merging it with any ranges it overlaps or touches.
+    pub fn insert(&mut self, range: Range<T>) {
+        if range.is_empty() {
+            return;
+        }
+        let lo = self.ranges.partition_point(|r| r.end < range.start);
+        let hi = self.ranges.partition_point(|r| r.start <= range.end);
+        let merged = if lo < hi {
+            self.ranges[lo].start.min(range.start)..self.ranges[hi - 1].end.max(range.end)
+        } else {
+            range
+        };
+        self.ranges.splice(lo..hi, [merged]);
+    }
+
+    /// Removes every value in `range` from the set, splitting ranges that straddle its ends.
+    pub fn remove(&mut self, range: Range<T>) {
+        if range.is_empty() {
+            return;
+        }
+        let lo = self.ranges.partition_point(|r| r.end <= range.start);
+        let hi = self.ranges.partition_point(|r| r.start < range.end);
+        if lo >= hi {
+            return;
+        }
+        let first_start = self.ranges[lo].start;
+        let last_end = self.ranges[hi - 1].end;
+        let remainders = [first_start..range.start, range.end..last_end]
+            .into_iter()
+            .filter(|r| !r.is_empty());
+        self.ranges.splice(lo..hi, remainders);
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        let idx = self.ranges.partition_point(|r| r.start <= value);
+        idx > 0 && value < self.ranges[idx - 1].end
+    }
+
+    /// Whether every value in `range` is in the set; empty ranges are always contained.
+    pub fn contains_range(&self, range: &Range<T>) -> bool {
+        if range.is_empty() {
+            return true;
+        }
+        let idx = self.ranges.partition_point(|r| r.start <= range.start);
+        idx > 0 && range.end <= self.ranges[idx - 1].end
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        IntervalSet {
+            ranges: self
+                .iter()
+                .merge_by(other.iter(), |a, b| a.start <= b.start)
+                .coalesce(|a, b| {
+                    if b.start <= a.end {
+                        Ok(a.start..a.end.max(b.end))
+                    } else {
+                        Err((a, b))
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let a = &self.ranges[i];
+            let b = &other.ranges[j];
+            let overlap = a.start.max(b.start)..a.end.min(b.end);
+            if !overlap.is_empty() {
+                ranges.push(overlap);
+            }
+            if a.end <= b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let mut j = 0;
+        for range in &self.ranges {
+            let mut start = range.start;
+            while j < other.ranges.len() && other.ranges[j].end <= start {
+                j += 1;
+            }
+            for cut in other.ranges[j..]
+                .iter()
+                .take_while(|cut| cut.start < range.end)
+            {
+                if start < cut.start {
+                    ranges.push(start..cut.start);
+                }
+                start = start.max(cut.end);
+            }
+            if start < range.end {
+                ranges.push(start..range.end);
+            }
+        }
+        IntervalSet { ranges }
+    }
+}
+
+impl<T: Copy + Ord + Sub<Output = T> + Sum> IntervalSet<T> {
+    /// The number of values in the set.
+    pub fn total_len(&self) -> T {
+        self.ranges.iter().map(|r| r.end - r.start).sum()
+    }
+}
+
+impl<T: Copy + Ord> FromIterator<Range<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut ranges: Vec<_> = iter.into_iter().filter(|r| !r.is_empty()).collect();
+        ranges.sort_unstable_by_key(|r| r.start);
+        ranges.dedup_by(|next, prev| {
+            if next.start <= prev.end {
+                prev.end = prev.end.max(next.end);
+                true
+            } else {
+                false
+            }
+        });
+        IntervalSet { ranges }
+    }
+}
+
+impl<T: Copy + Ord> Extend<Range<T>> for IntervalSet<T> {
+    fn extend<I: IntoIterator<Item = Range<T>>>(&mut self, iter: I) {
+        for range in iter {
+            self.insert(range);
+        }
+    }
+}
+
+/// Maps values by adding the offset of whichever disjoint segment contains them, passing values
+/// outside every segment through unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PiecewiseMap<T> {
+    segments: Vec<(Range<T>, T)>,
+}
+
+impl<T: Copy + Ord + Add<Output = T>> PiecewiseMap<T> {
+    pub fn new(segments: impl IntoIterator<Item = (Range<T>, T)>) -> Result<Self> {
+        let mut segments: Vec<_> = segments
+            .into_iter()
+            .filter(|(r, _)| !r.is_empty())
+            .collect();
+        segments.sort_unstable_by_key(|(r, _)| r.start);
+        if segments.windows(2).any(|w| w[1].0.start < w[0].0.end) {
+            return Err(anyhow!("PiecewiseMap segments overlap"));
+        }
+        Ok(PiecewiseMap { segments })
+    }
+
+    pub fn segments(&self) -> &[(Range<T>, T)] {
+        &self.segments
+    }
+
+    pub fn map(&self, value: T) -> T {
+        let idx = self.segments.partition_point(|(r, _)| r.start <= value);
+        match self.segments[..idx].last() {
+            Some((r, offset)) if value < r.end => value + *offset,
+            _ => value,
+        }
+    }
+
+    /// Maps every value in `range`, splitting it wherever it crosses a segment boundary. The
+    /// pieces come out in input order, so they may overlap or be unsorted once mapped.
+    pub fn map_range(&self, range: Range<T>) -> Vec<Range<T>> {
+        let mut out = Vec::new();
+        if range.is_empty() {
+            return out;
+        }
+        let mut cursor = range.start;
+        let first = self.segments.partition_point(|(r, _)| r.end <= range.start);
+        for (segment, offset) in self.segments[first..]
+            .iter()
+            .take_while(|(r, _)| r.start < range.end)
+        {
+            if cursor < segment.start {
+                out.push(cursor..segment.start);
+                cursor = segment.start;
+            }
+            let end = segment.end.min(range.end);
+            out.push(cursor + *offset..end + *offset);
+            cursor = end;
+        }
+        if cursor < range.end {
+            out.push(cursor..range.end);
+        }
+        out
+    }
+
+    pub fn map_set(&self, set: &IntervalSet<T>) -> IntervalSet<T> {
+        set.iter().flat_map(|r| self.map_range(r)).collect()
+    }
+}
+
+#[test]
+fn test_insert_and_remove() {
+    let mut set = IntervalSet::new();
+    set.insert(10..15);
+    set.insert(1..3);
+    set.insert(3..5);
+    set.insert(20..25);
+    assert_eq!(set.ranges(), &[1..5, 10..15, 20..25]);
+    set.insert(4..21);
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![1..25]);
+    set.remove(5..10);
+    set.remove(12..13);
+    assert_eq!(set.ranges(), &[1..5, 10..12, 13..25]);
+    set.remove(0..100);
+    assert!(set.is_empty());
+}
+
+#[test]
+fn test_queries() {
+    let set: IntervalSet<u64> = [3..6, 10..15, 16..21, 12..19].into_iter().collect();
+    assert_eq!(set.ranges(), &[3..6, 10..21]);
+    assert_eq!(set.total_len(), 14);
+    assert!(set.contains(3) && set.contains(20));
+    assert!(!set.contains(6) && !set.contains(0) && !set.contains(21));
+    assert!(set.contains_range(&(11..21)));
+    assert!(!set.contains_range(&(5..11)));
+}
+
+#[test]
+fn test_set_algebra() {
+    let a: IntervalSet<i32> = [0..10, 20..30].into_iter().collect();
+    let b: IntervalSet<i32> = [5..25, 28..29, 40..45].into_iter().collect();
+    assert_eq!(a.union(&b).ranges(), &[0..30, 40..45]);
+    assert_eq!(a.intersection(&b).ranges(), &[5..10, 20..25, 28..29]);
+    assert_eq!(a.difference(&b).ranges(), &[0..5, 25..28, 29..30]);
+    assert_eq!(b.difference(&a).ranges(), &[10..20, 40..45]);
+}
+
+#[test]
+fn test_piecewise_map() {
+    let map = PiecewiseMap::new([(98..100, -48), (50..98, 2)]).unwrap();
+    assert_eq!(map.map(79), 81);
+    assert_eq!(map.map(99), 51);
+    assert_eq!(map.map(10), 10);
+    assert_eq!(map.map_range(40..100), vec![40..50, 52..100, 50..52]);
+    let set: IntervalSet<i64> = [79..93, 55..68].into_iter().collect();
+    assert_eq!(map.map_set(&set).ranges(), &[57..70, 81..95]);
+    assert!(PiecewiseMap::new([(0..10, 1), (5..15, 2)]).is_err());
+}
@@ -1,9 +1,57 @@
-use std::{fmt::Display, fs::read_to_string, path::PathBuf, time::Instant};
+use std::{borrow::Cow, fmt::Display, fs::read_to_string, path::PathBuf, time::Instant};
 
 use anyhow::{Context, Result};
 use clap::{Arg, ArgAction, Command, value_parser};
 
 pub mod byte_grid;
+pub mod exact_cover;
+pub mod interval_set;
+
+/// What to do with the newline(s) at the end of an input file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrailingNewline {
+    /// Leave the end of the input as-is.
+    #[default]
+    Keep,
+    /// Remove every trailing newline.
+    Strip,
+    /// Collapse trailing newlines into exactly one, adding it if missing.
+    Ensure,
+}
+
+/// Strips a leading byte-order mark, rewrites CRLF line endings as LF, then applies `trailing`,
+/// only allocating when something actually had to change.
+pub fn normalize_input(s: &str, trailing: TrailingNewline) -> Cow<'_, str> {
+    let s = s.strip_prefix('\u{feff}').unwrap_or(s);
+    let mut s = if s.contains('\r') {
+        Cow::Owned(s.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(s)
+    };
+    match trailing {
+        TrailingNewline::Keep => {}
+        TrailingNewline::Strip => {
+            let len = s.trim_end_matches('\n').len();
+            truncate_cow(&mut s, len);
+        }
+        TrailingNewline::Ensure => {
+            let len = s.trim_end_matches('\n').len();
+            if len < s.len() {
+                truncate_cow(&mut s, len + 1);
+            } else {
+                s.to_mut().push('\n');
+            }
+        }
+    }
+    s
+}
+
+fn truncate_cow(s: &mut Cow<'_, str>, len: usize) {
+    match s {
+        Cow::Borrowed(b) => *b = &b[..len],
+        Cow::Owned(o) => o.truncate(len),
+    }
+}
 
 pub fn run_day<F1, R1, F2, R2>(part1: F1, part2: F2) -> Result<()>
 where
@@ -23,12 +71,27 @@ where
                 .long("part_two")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("trailing_newline")
+                .long("trailing_newline")
+                .value_parser(["keep", "strip", "ensure"])
+                .default_value("keep"),
+        )
         .get_matches();
     let path = matches
         .get_one::<PathBuf>("input_path")
         .context("Failed to parse path from command invocation")?;
-    let s =
+    let trailing = match matches
+        .get_one::<String>("trailing_newline")
+        .map(String::as_str)
+    {
+        Some("strip") => TrailingNewline::Strip,
+        Some("ensure") => TrailingNewline::Ensure,
+        _ => TrailingNewline::Keep,
+    };
+    let raw =
         read_to_string(path).with_context(|| format!("Failed to read from {}", path.display()))?;
+    let s = normalize_input(&raw, trailing);
     if matches.get_flag("part_two") {
         let start = Instant::now();
         let res = part2(&s);
@@ -52,3 +115,25 @@ fn render_result_and_duration<T: Display>(duration: f64, res: Result<T>) -> Resu
         println!("{}", r);
     })
 }
+
+#[test]
+fn test_normalize_input() {
+    let windows = "\u{feff}ab\r\ncd\r\n\r\n";
+    assert_eq!(
+        normalize_input(windows, TrailingNewline::Keep),
+        "ab\ncd\n\n"
+    );
+    assert_eq!(normalize_input(windows, TrailingNewline::Strip), "ab\ncd");
+    assert_eq!(
+        normalize_input(windows, TrailingNewline::Ensure),
+        "ab\ncd\n"
+    );
+    assert_eq!(
+        normalize_input("ab\ncd", TrailingNewline::Ensure),
+        "ab\ncd\n"
+    );
+    assert!(matches!(
+        normalize_input("ab\ncd\n", TrailingNewline::Strip),
+        Cow::Borrowed("ab\ncd")
+    ));
+}
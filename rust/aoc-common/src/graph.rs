@@ -0,0 +1,295 @@
+//! A directed graph over interned node labels, with the usual queries on it.
+
+use anyhow::{anyhow, Result};
+use std::{
+    collections::{hash_map::RandomState, HashMap, VecDeque},
+    hash::{BuildHasher, Hash},
+};
+
+/// A directed graph whose node labels are interned into dense `usize` ids, handed out in the order
+/// the labels are first seen. `S` hashes the labels, so each year can plug in the hasher it
+/// already uses.
+#[derive(Clone, Debug)]
+pub struct Graph<L, S = RandomState> {
+    ids: HashMap<L, usize, S>,
+    labels: Vec<L>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl<L, S: Default> Default for Graph<L, S> {
+    fn default() -> Self {
+        Graph {
+            ids: HashMap::default(),
+            labels: Vec::new(),
+            adjacency: Vec::new(),
+        }
+    }
+}
+
+impl<L: Hash + Eq + Clone, S: BuildHasher + Default> Graph<L, S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the id for `label`, adding it as a new node if it hasn't been seen before.
+    pub fn intern(&mut self, label: L) -> usize {
+        if let Some(&id) = self.ids.get(&label) {
+            return id;
+        }
+        let id = self.labels.len();
+        self.ids.insert(label.clone(), id);
+        self.labels.push(label);
+        self.adjacency.push(Vec::new());
+        id
+    }
+
+    pub fn id(&self, label: &L) -> Option<usize> {
+        self.ids.get(label).copied()
+    }
+
+    pub fn add_edge(&mut self, from: L, to: L) {
+        let from = self.intern(from);
+        let to = self.intern(to);
+        self.adjacency[from].push(to);
+    }
+}
+
+impl<L, S> Graph<L, S> {
+    pub fn label(&self, id: usize) -> &L {
+        &self.labels[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn neighbours(&self, id: usize) -> &[usize] {
+        &self.adjacency[id]
+    }
+
+    /// Kahn's algorithm; returns `None` if the graph has a cycle.
+    pub fn topological_order(&self) -> Option<Vec<usize>> {
+        let mut in_degree = vec![0_usize; self.len()];
+        for &to in self.adjacency.iter().flatten() {
+            in_degree[to] += 1;
+        }
+        let mut ready: VecDeque<usize> = (0..self.len()).filter(|&id| in_degree[id] == 0).collect();
+        let mut order = Vec::with_capacity(self.len());
+        while let Some(id) = ready.pop_front() {
+            order.push(id);
+            for &to in &self.adjacency[id] {
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    ready.push_back(to);
+                }
+            }
+        }
+        (order.len() == self.len()).then_some(order)
+    }
+
+    /// Returns the nodes of some cycle in edge order, or `None` if the graph is acyclic.
+    pub fn find_cycle(&self) -> Option<Vec<usize>> {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Colour {
+            Unvisited,
+            OnPath,
+            Done,
+        }
+        let mut colour = vec![Colour::Unvisited; self.len()];
+        let mut path: Vec<(usize, usize)> = Vec::new();
+        for root in 0..self.len() {
+            if colour[root] != Colour::Unvisited {
+                continue;
+            }
+            colour[root] = Colour::OnPath;
+            path.push((root, 0));
+            while let Some(&(id, edge)) = path.last() {
+                let Some(&next) = self.adjacency[id].get(edge) else {
+                    colour[id] = Colour::Done;
+                    path.pop();
+                    continue;
+                };
+                path.last_mut().unwrap().1 += 1;
+                match colour[next] {
+                    Colour::Unvisited => {
+                        colour[next] = Colour::OnPath;
+                        path.push((next, 0));
+                    }
+                    Colour::OnPath => {
+                        let start = path.iter().position(|&(id, _)| id == next).unwrap();
+                        return Some(path[start..].iter().map(|&(id, _)| id).collect());
+                    }
+                    Colour::Done => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// For every node, the number of distinct paths from it to `to`. Errors if the graph has a
+    /// cycle, since the counts could then be infinite.
+    pub fn path_counts_to(&self, to: usize) -> Result<Vec<u64>> {
+        let order = self
+            .topological_order()
+            .ok_or_else(|| anyhow!("Can't count paths in a cyclic graph"))?;
+        let mut counts = vec![0_u64; self.len()];
+        counts[to] = 1;
+        for &id in order.iter().rev() {
+            if id != to {
+                counts[id] = self.adjacency[id].iter().map(|&next| counts[next]).sum();
+            }
+        }
+        Ok(counts)
+    }
+
+    pub fn count_paths(&self, from: usize, to: usize) -> Result<u64> {
+        Ok(self.path_counts_to(to)?[from])
+    }
+
+    /// Tarjan's algorithm, run iteratively. Components come out in reverse topological order.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        const UNVISITED: usize = usize::MAX;
+        let mut index = vec![UNVISITED; self.len()];
+        let mut low_link = vec![0; self.len()];
+        let mut on_stack = vec![false; self.len()];
+        let mut stack = Vec::new();
+        let mut call_stack: Vec<(usize, usize)> = Vec::new();
+        let mut next_index = 0;
+        let mut components = Vec::new();
+
+        for root in 0..self.len() {
+            if index[root] != UNVISITED {
+                continue;
+            }
+            index[root] = next_index;
+            low_link[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            call_stack.push((root, 0));
+
+            while let Some(&(id, edge)) = call_stack.last() {
+                if let Some(&next) = self.adjacency[id].get(edge) {
+                    call_stack.last_mut().unwrap().1 += 1;
+                    if index[next] == UNVISITED {
+                        index[next] = next_index;
+                        low_link[next] = next_index;
+                        next_index += 1;
+                        stack.push(next);
+                        on_stack[next] = true;
+                        call_stack.push((next, 0));
+                    } else if on_stack[next] {
+                        low_link[id] = low_link[id].min(index[next]);
+                    }
+                    continue;
+                }
+
+                call_stack.pop();
+                if let Some(&(parent, _)) = call_stack.last() {
+                    low_link[parent] = low_link[parent].min(low_link[id]);
+                }
+                if low_link[id] == index[id] {
+                    let mut component = Vec::new();
+                    loop {
+                        let member = stack.pop().unwrap();
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == id {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+
+    /// Marks every node reachable from `start`, including `start` itself.
+    pub fn reachable_from(&self, start: usize) -> Vec<bool> {
+        let mut seen = vec![false; self.len()];
+        seen[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(id) = queue.pop_front() {
+            for &next in &self.adjacency[id] {
+                if !seen[next] {
+                    seen[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn from_edges(edges: &[(&'static str, &'static str)]) -> Graph<&'static str> {
+        let mut graph = Graph::new();
+        for &(from, to) in edges {
+            graph.add_edge(from, to);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_interning() {
+        let graph = from_edges(&[("aaa", "bbb"), ("bbb", "ccc"), ("aaa", "ccc")]);
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph.id(&"ccc"), Some(2));
+        assert_eq!(graph.id(&"zzz"), None);
+        assert_eq!(*graph.label(1), "bbb");
+        assert_eq!(graph.neighbours(0), &[1, 2]);
+    }
+
+    #[test]
+    fn test_dag_queries() {
+        let graph = from_edges(&[
+            ("you", "bbb"),
+            ("you", "ccc"),
+            ("bbb", "ddd"),
+            ("ccc", "ddd"),
+            ("ddd", "out"),
+            ("ccc", "out"),
+            ("zzz", "you"),
+        ]);
+        let id = |label| graph.id(&label).unwrap();
+        let order = graph.topological_order().unwrap();
+        let position = |label| order.iter().position(|&x| x == id(label)).unwrap();
+        assert!(position("zzz") < position("you"));
+        assert!(position("ddd") < position("out"));
+        assert_eq!(graph.find_cycle(), None);
+        assert_eq!(graph.count_paths(id("you"), id("out")).unwrap(), 3);
+        assert_eq!(graph.count_paths(id("out"), id("you")).unwrap(), 0);
+        let reachable = graph.reachable_from(id("ccc"));
+        assert!(reachable[id("out")] && !reachable[id("bbb")]);
+    }
+
+    #[test]
+    fn test_cycles_and_components() {
+        let graph = from_edges(&[
+            ("aaa", "bbb"),
+            ("bbb", "ccc"),
+            ("ccc", "aaa"),
+            ("ccc", "ddd"),
+            ("ddd", "eee"),
+            ("eee", "ddd"),
+        ]);
+        assert!(graph.topological_order().is_none());
+        assert!(graph.count_paths(0, 3).is_err());
+        let cycle = graph.find_cycle().unwrap();
+        for (i, &id) in cycle.iter().enumerate() {
+            assert!(graph.neighbours(id).contains(&cycle[(i + 1) % cycle.len()]));
+        }
+
+        let mut components = graph.strongly_connected_components();
+        components.iter_mut().for_each(|c| c.sort_unstable());
+        assert_eq!(components, vec![vec![3, 4], vec![0, 1, 2]]);
+    }
+}
@@ -0,0 +1,86 @@
+//! Reading puzzle input: normalising line endings and checking the shape of each line before an
+//! unchecked fast path indexes into it.
+
+use anyhow::{bail, ensure, Context, Result};
+use std::borrow::Cow;
+
+/// Strips a leading byte-order mark and rewrites CRLF line endings as LF, only allocating when
+/// `input` actually contains a `\r`. The aoc-runner harness hands us the raw file, so every reader
+/// that relies on `\n`-only lines or a fixed row stride should go through this first.
+pub fn normalize_input(input: &str) -> Cow<'_, str> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    if input.contains('\r') {
+        Cow::Owned(input.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Runs `check_line` over every line of `input`, along with its 0-based line number, so solutions
+/// with unchecked fast paths can reject malformed input up front. A single trailing newline is
+/// allowed but not required; carriage returns are always rejected, so normalize first.
+pub fn validate_lines(
+    input: &str,
+    mut check_line: impl FnMut(usize, &[u8]) -> Result<()>,
+) -> Result<()> {
+    if let Some(pos) = input.find('\r') {
+        bail!("Unexpected carriage return at byte {pos}");
+    }
+    let body = input.strip_suffix('\n').unwrap_or(input);
+    for (line_no, line) in body.split('\n').enumerate() {
+        check_line(line_no, line.as_bytes()).with_context(|| format!("Line {}", line_no + 1))?;
+    }
+    Ok(())
+}
+
+/// Checks that `line` is exactly `len` bytes long and that every byte passes `is_valid`, which
+/// receives the byte's column alongside the byte itself.
+pub fn check_line_shape(
+    line: &[u8],
+    len: usize,
+    is_valid: impl Fn(usize, u8) -> bool,
+) -> Result<()> {
+    ensure!(
+        line.len() == len,
+        "Expected {len} bytes, found {}",
+        line.len()
+    );
+    match line
+        .iter()
+        .copied()
+        .enumerate()
+        .find(|&(col, byte)| !is_valid(col, byte))
+    {
+        Some((col, byte)) => bail!("Unexpected byte {:?} in column {}", byte as char, col + 1),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_input() {
+        assert!(matches!(
+            normalize_input("ab\ncd\n"),
+            Cow::Borrowed("ab\ncd\n")
+        ));
+        assert_eq!(normalize_input("\u{feff}ab\r\ncd\r\n"), "ab\ncd\n");
+        assert_eq!(normalize_input("\u{feff}ab"), "ab");
+    }
+
+    #[test]
+    fn test_validate_lines() {
+        let digits = |_, line: &[u8]| check_line_shape(line, 2, |_, byte| byte.is_ascii_digit());
+        assert!(validate_lines("12\n34\n", digits).is_ok());
+        assert!(validate_lines("12\n34", digits).is_ok());
+        assert!(validate_lines("12\r\n34", digits).is_err());
+        assert!(validate_lines("12\n345\n", digits).is_err());
+        let error = validate_lines("12\n3x\n", digits).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "Line 2: Unexpected byte 'x' in column 2"
+        );
+    }
+}
@@ -0,0 +1,5 @@
+//! Helpers shared by every year's solutions. Each year is its own crate with its own lockfile and
+//! nom version, so this one sticks to `std` and `anyhow` and each year depends on it by path.
+
+pub mod graph;
+pub mod input;